@@ -0,0 +1,165 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::{
+    utils::{storage, yt_dlp::RuntimeCapabilities},
+    yt_dlp_args::build_archive_key_args,
+    yt_dlp_probe::run_probe,
+};
+
+const OUTPUT_DIR_ARCHIVE_FILE: &str = ".yt-dlp-archive.txt";
+const GLOBAL_ARCHIVE_FILE: &str = "download-archive.txt";
+
+/// Where `--download-archive` keeps the `extractor id` lines of finished downloads.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ArchiveScope {
+    #[default]
+    Off,
+    /// A hidden file inside each download directory.
+    OutputDir,
+    /// One archive in the app data dir shared by every download directory.
+    Global,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchivedItem {
+    pub extractor: String,
+    pub id: String,
+    pub archived: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateCheck {
+    pub archive_path: Option<String>,
+    pub already_downloaded: bool,
+    pub items: Vec<ArchivedItem>,
+}
+
+pub fn archive_path(scope: ArchiveScope, output_dir: &Path) -> Result<Option<PathBuf>, String> {
+    match scope {
+        ArchiveScope::Off => Ok(None),
+        ArchiveScope::OutputDir => Ok(Some(output_dir.join(OUTPUT_DIR_ARCHIVE_FILE))),
+        ArchiveScope::Global => storage::data_file_path(GLOBAL_ARCHIVE_FILE).map(Some),
+    }
+}
+
+/// Resolves the extractor and id of `url` (every entry for a playlist) without
+/// downloading anything, then looks each one up in the archive.
+pub async fn check(
+    binary_path: &Path,
+    url: &str,
+    browser: Option<&str>,
+    playlist: bool,
+    runtime_caps: &RuntimeCapabilities,
+    archive_path: Option<&Path>,
+) -> Result<DuplicateCheck, String> {
+    let args = build_archive_key_args(url, browser, playlist, runtime_caps);
+    let stdout = run_probe(binary_path, args).await?;
+
+    let archived = match archive_path {
+        Some(path) => read_archive(path).await?,
+        None => Vec::new(),
+    };
+    let items: Vec<ArchivedItem> = parse_archive_keys(&stdout)
+        .into_iter()
+        .map(|(extractor, id)| {
+            let key = archive_key(&extractor, &id);
+            ArchivedItem {
+                archived: archived.contains(&key),
+                extractor,
+                id,
+            }
+        })
+        .collect();
+
+    Ok(DuplicateCheck {
+        archive_path: archive_path.map(|path| path.to_string_lossy().to_string()),
+        already_downloaded: !items.is_empty() && items.iter().all(|item| item.archived),
+        items,
+    })
+}
+
+/// Same key yt-dlp writes: the lowercased extractor key, a space, then the id.
+pub fn archive_key(extractor: &str, id: &str) -> String {
+    format!("{} {id}", extractor.to_lowercase())
+}
+
+async fn read_archive(path: &Path) -> Result<Vec<String>, String> {
+    match tokio::fs::read_to_string(path).await {
+        Ok(content) => Ok(content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(format!("读取 {} 失败: {err}", path.display())),
+    }
+}
+
+/// Parses the `extractor id` lines printed by `build_archive_key_args`; entries
+/// yt-dlp could not resolve print `NA` and are skipped.
+fn parse_archive_keys(output: &str) -> Vec<(String, String)> {
+    output
+        .lines()
+        .filter_map(|line| line.trim().split_once(' '))
+        .map(|(extractor, id)| (extractor.trim(), id.trim()))
+        .filter(|(extractor, id)| {
+            !extractor.is_empty() && !id.is_empty() && *extractor != "NA" && *id != "NA"
+        })
+        .map(|(extractor, id)| (extractor.to_string(), id.to_string()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{archive_key, archive_path, parse_archive_keys, read_archive, ArchiveScope};
+    use std::path::Path;
+
+    #[test]
+    fn builds_keys_compatible_with_yt_dlp() {
+        assert_eq!(archive_key("Youtube", "dQw4w9WgXcQ"), "youtube dQw4w9WgXcQ");
+        assert_eq!(
+            archive_key("BiliBili", "BV1xx411c7mD"),
+            "bilibili BV1xx411c7mD"
+        );
+    }
+
+    #[test]
+    fn parses_printed_keys_and_skips_unresolved_entries() {
+        let output = "Youtube dQw4w9WgXcQ\nNA abc\nYoutube NA\n\nYoutube kJQP7kiw5Fk\n";
+        assert_eq!(
+            parse_archive_keys(output),
+            [
+                ("Youtube".to_string(), "dQw4w9WgXcQ".to_string()),
+                ("Youtube".to_string(), "kJQP7kiw5Fk".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn reads_archive_lines_and_tolerates_missing_file() {
+        let dir = std::env::temp_dir().join(format!("yt-dlp-x-archive-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("create dir");
+        let path = archive_path(ArchiveScope::OutputDir, &dir)
+            .expect("path")
+            .expect("output dir scope has a path");
+        std::fs::write(&path, "youtube dQw4w9WgXcQ\r\n\nbilibili BV1xx411c7mD\n").expect("write");
+
+        let lines = read_archive(&path).await.expect("read");
+        assert!(lines.contains(&archive_key("Youtube", "dQw4w9WgXcQ")));
+        assert!(lines.contains(&archive_key("BiliBili", "BV1xx411c7mD")));
+
+        std::fs::remove_file(&path).expect("remove");
+        assert!(read_archive(&path).await.expect("missing file").is_empty());
+        assert_eq!(
+            archive_path(ArchiveScope::Off, Path::new("/tmp")).expect("path"),
+            None
+        );
+
+        std::fs::remove_dir_all(&dir).expect("remove dir");
+    }
+}
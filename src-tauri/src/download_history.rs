@@ -0,0 +1,445 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tokio::{
+    fs::{self, OpenOptions},
+    io::AsyncWriteExt,
+    sync::{Mutex, MutexGuard},
+};
+
+use crate::{
+    utils::storage, yt_dlp_progress::DownloadedItem, DownloadMode, DownloadRequest, DownloadStatus,
+    VideoQuality,
+};
+
+const HISTORY_FILE: &str = "history.jsonl";
+const DEFAULT_PAGE_SIZE: usize = 50;
+const MAX_PAGE_SIZE: usize = 500;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryEntry {
+    pub id: String,
+    pub session_id: String,
+    pub url: String,
+    pub extractor: Option<String>,
+    pub video_id: Option<String>,
+    pub title: Option<String>,
+    pub files: Vec<String>,
+    pub size: Option<u64>,
+    pub mode: DownloadMode,
+    pub quality: VideoQuality,
+    pub output_dir: String,
+    pub started_at: u64,
+    pub finished_at: u64,
+    pub status: DownloadStatus,
+    pub exit_code: Option<i32>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryPage {
+    pub entries: Vec<HistoryEntry>,
+    pub total: usize,
+}
+
+/// Everything known about a finished session before it is split into one
+/// entry per downloaded item.
+pub struct FinishedSession<'a> {
+    pub session_id: &'a str,
+    pub request: &'a DownloadRequest,
+    pub output_dir: &'a Path,
+    pub started_at: u64,
+    pub finished_at: u64,
+    pub status: DownloadStatus,
+    pub exit_code: Option<i32>,
+}
+
+/// Finished downloads, one JSON object per line so recording a download is a
+/// plain append; only deletes rewrite the file.
+pub struct DownloadHistory {
+    path: Option<PathBuf>,
+    lock: Mutex<()>,
+}
+
+impl DownloadHistory {
+    pub fn load() -> Self {
+        let path = storage::data_file_path(HISTORY_FILE)
+            .map_err(|err| eprintln!("{err}"))
+            .ok();
+
+        Self {
+            path,
+            lock: Mutex::new(()),
+        }
+    }
+
+    pub async fn record(&self, session: FinishedSession<'_>, items: &[DownloadedItem]) {
+        let entries = entries_for_session(session, items).await;
+        if let Err(err) = self.append(&entries).await {
+            eprintln!("保存下载历史失败: {err}");
+        }
+    }
+
+    /// Newest first; `query` matches title, URL, extractor, video id and file
+    /// paths case-insensitively.
+    pub async fn list(
+        &self,
+        query: Option<&str>,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> Result<HistoryPage, String> {
+        let _guard = self.lock().await;
+        let query = query
+            .map(|value| value.trim().to_lowercase())
+            .filter(|value| !value.is_empty());
+
+        let mut matches: Vec<HistoryEntry> = self
+            .read_all()
+            .await?
+            .into_iter()
+            .filter(|entry| {
+                query
+                    .as_deref()
+                    .is_none_or(|query| matches_query(entry, query))
+            })
+            .collect();
+        matches.reverse();
+
+        let total = matches.len();
+        let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+        let entries = matches.into_iter().skip(offset).take(limit).collect();
+
+        Ok(HistoryPage { entries, total })
+    }
+
+    pub async fn delete(&self, id: &str) -> Result<(), String> {
+        let _guard = self.lock().await;
+        let mut entries = self.read_all().await?;
+        let before = entries.len();
+        entries.retain(|entry| entry.id != id);
+
+        if entries.len() == before {
+            return Err("未找到该下载记录".into());
+        }
+
+        self.write_all(&entries).await
+    }
+
+    pub async fn clear(&self) -> Result<(), String> {
+        let _guard = self.lock().await;
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        match fs::remove_file(path).await {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(format!("清空下载历史失败: {err}")),
+        }
+    }
+
+    async fn append(&self, entries: &[HistoryEntry]) -> Result<(), String> {
+        let _guard = self.lock().await;
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .await
+                .map_err(|err| format!("创建目录失败: {err}"))?;
+        }
+
+        let content = to_json_lines(entries)?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await
+            .map_err(|err| format!("写入 {} 失败: {err}", path.display()))?;
+        file.write_all(&content)
+            .await
+            .map_err(|err| format!("写入 {} 失败: {err}", path.display()))
+    }
+
+    /// Skips lines that do not parse, e.g. one cut short by a crash mid-append.
+    async fn read_all(&self) -> Result<Vec<HistoryEntry>, String> {
+        let Some(path) = &self.path else {
+            return Ok(Vec::new());
+        };
+
+        let content = match fs::read_to_string(path).await {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(format!("读取 {} 失败: {err}", path.display())),
+        };
+
+        Ok(content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+
+    async fn write_all(&self, entries: &[HistoryEntry]) -> Result<(), String> {
+        let Some(path) = self.path.clone() else {
+            return Ok(());
+        };
+
+        let content = to_json_lines(entries)?;
+        tokio::task::spawn_blocking(move || storage::write_atomic(&path, &content))
+            .await
+            .map_err(|err| format!("保存下载历史失败: {err}"))?
+    }
+
+    async fn lock(&self) -> MutexGuard<'_, ()> {
+        self.lock.lock().await
+    }
+}
+
+async fn entries_for_session(
+    session: FinishedSession<'_>,
+    items: &[DownloadedItem],
+) -> Vec<HistoryEntry> {
+    let FinishedSession {
+        session_id,
+        request,
+        output_dir,
+        started_at,
+        finished_at,
+        status,
+        exit_code,
+    } = session;
+
+    let mut sizes = Vec::with_capacity(items.len());
+    for item in items {
+        let size = match &item.filepath {
+            Some(file) => fs::metadata(file).await.ok().map(|metadata| metadata.len()),
+            None => None,
+        };
+        sizes.push(size);
+    }
+
+    let entry = |index: usize, item: Option<&DownloadedItem>| {
+        let files: Vec<String> = item
+            .and_then(|item| item.filepath.clone())
+            .into_iter()
+            .collect();
+        let size = sizes.get(index).copied().flatten();
+
+        HistoryEntry {
+            id: format!("{session_id}-{index}"),
+            session_id: session_id.to_string(),
+            url: item
+                .and_then(|item| item.webpage_url.clone())
+                .unwrap_or_else(|| request.url.clone()),
+            extractor: item.and_then(|item| item.extractor.clone()),
+            video_id: item.and_then(|item| item.id.clone()),
+            title: item.and_then(|item| item.title.clone()),
+            files,
+            size,
+            mode: request.mode,
+            quality: request.quality,
+            output_dir: output_dir.to_string_lossy().to_string(),
+            started_at,
+            finished_at,
+            status,
+            exit_code,
+        }
+    };
+
+    if items.is_empty() {
+        return vec![entry(0, None)];
+    }
+
+    items
+        .iter()
+        .enumerate()
+        .map(|(index, item)| entry(index, Some(item)))
+        .collect()
+}
+
+fn to_json_lines(entries: &[HistoryEntry]) -> Result<Vec<u8>, String> {
+    let mut content = Vec::new();
+    for entry in entries {
+        serde_json::to_writer(&mut content, entry)
+            .map_err(|err| format!("序列化下载记录失败: {err}"))?;
+        content.push(b'\n');
+    }
+    Ok(content)
+}
+
+fn matches_query(entry: &HistoryEntry, query: &str) -> bool {
+    let fields = [
+        entry.title.as_deref(),
+        Some(entry.url.as_str()),
+        entry.extractor.as_deref(),
+        entry.video_id.as_deref(),
+    ];
+
+    fields
+        .into_iter()
+        .flatten()
+        .chain(entry.files.iter().map(String::as_str))
+        .any(|value| value.to_lowercase().contains(query))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DownloadHistory, FinishedSession};
+    use crate::{yt_dlp_progress::DownloadedItem, DownloadRequest, DownloadStatus};
+    use serde_json::json;
+    use std::path::{Path, PathBuf};
+    use tokio::sync::Mutex;
+
+    fn history_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("yt-dlp-x-history-{}-{name}", std::process::id()))
+    }
+
+    fn history(name: &str) -> DownloadHistory {
+        let dir = history_dir(name);
+        let _ = std::fs::remove_dir_all(&dir);
+
+        DownloadHistory {
+            path: Some(dir.join("history.jsonl")),
+            lock: Mutex::new(()),
+        }
+    }
+
+    fn cleanup(name: &str) {
+        let _ = std::fs::remove_dir_all(history_dir(name));
+    }
+
+    fn request(url: &str) -> DownloadRequest {
+        serde_json::from_value(json!({ "url": url, "mode": "video" })).expect("valid request")
+    }
+
+    fn item(id: &str, title: &str) -> DownloadedItem {
+        DownloadedItem {
+            extractor: Some("Youtube".into()),
+            id: Some(id.into()),
+            title: Some(title.into()),
+            filepath: Some(format!("/tmp/out/{title} [{id}].mp4")),
+            webpage_url: Some(format!("https://www.youtube.com/watch?v={id}")),
+            video_codec: Some("avc1.64001F".into()),
+        }
+    }
+
+    async fn record(history: &DownloadHistory, session_id: &str, items: &[DownloadedItem]) {
+        let request = request("https://www.youtube.com/playlist?list=PL1");
+        history
+            .record(
+                FinishedSession {
+                    session_id,
+                    request: &request,
+                    output_dir: Path::new("/tmp/out"),
+                    started_at: 1,
+                    finished_at: 2,
+                    status: DownloadStatus::Completed,
+                    exit_code: Some(0),
+                },
+                items,
+            )
+            .await;
+    }
+
+    #[tokio::test]
+    async fn records_one_entry_per_item_and_pages_newest_first() {
+        let history = history("paging");
+        record(
+            &history,
+            "a",
+            &[item("id1", "First"), item("id2", "Second")],
+        )
+        .await;
+        record(&history, "b", &[item("id3", "Third")]).await;
+
+        let page = history.list(None, 0, Some(2)).await.expect("list");
+        assert_eq!(page.total, 3);
+        let titles: Vec<_> = page
+            .entries
+            .iter()
+            .map(|entry| entry.title.as_deref().unwrap_or_default())
+            .collect();
+        assert_eq!(titles, ["Third", "Second"]);
+
+        let page = history.list(None, 2, Some(2)).await.expect("list");
+        assert_eq!(page.entries.len(), 1);
+        assert_eq!(page.entries[0].video_id.as_deref(), Some("id1"));
+        assert_eq!(page.entries[0].id, "a-0");
+
+        cleanup("paging");
+    }
+
+    #[tokio::test]
+    async fn searches_across_title_url_and_ids() {
+        let history = history("search");
+        record(
+            &history,
+            "a",
+            &[item("dQw4w9WgXcQ", "Never Gonna Give You Up")],
+        )
+        .await;
+        record(&history, "b", &[item("kJQP7kiw5Fk", "Despacito")]).await;
+
+        let by_title = history
+            .list(Some("never gonna"), 0, None)
+            .await
+            .expect("list");
+        assert_eq!(by_title.total, 1);
+        assert_eq!(by_title.entries[0].session_id, "a");
+
+        let by_id = history.list(Some("KJQP7"), 0, None).await.expect("list");
+        assert_eq!(by_id.total, 1);
+        assert_eq!(by_id.entries[0].session_id, "b");
+
+        assert_eq!(
+            history
+                .list(Some("youtube"), 0, None)
+                .await
+                .expect("list")
+                .total,
+            2
+        );
+
+        cleanup("search");
+    }
+
+    #[tokio::test]
+    async fn failed_sessions_without_items_keep_the_request_url() {
+        let history = history("failed");
+        record(&history, "a", &[]).await;
+
+        let page = history.list(None, 0, None).await.expect("list");
+        assert_eq!(page.total, 1);
+        assert_eq!(
+            page.entries[0].url,
+            "https://www.youtube.com/playlist?list=PL1"
+        );
+        assert!(page.entries[0].files.is_empty());
+
+        cleanup("failed");
+    }
+
+    #[tokio::test]
+    async fn deletes_and_clears_entries() {
+        let history = history("delete");
+        record(
+            &history,
+            "a",
+            &[item("id1", "First"), item("id2", "Second")],
+        )
+        .await;
+
+        history.delete("a-0").await.expect("delete");
+        assert!(history.delete("a-0").await.is_err());
+        let page = history.list(None, 0, None).await.expect("list");
+        assert_eq!(page.total, 1);
+        assert_eq!(page.entries[0].id, "a-1");
+
+        history.clear().await.expect("clear");
+        assert_eq!(history.list(None, 0, None).await.expect("list").total, 0);
+        history.clear().await.expect("clearing twice is fine");
+
+        cleanup("delete");
+    }
+}
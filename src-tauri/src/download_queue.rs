@@ -0,0 +1,331 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use crate::{now_millis, utils::storage, DownloadRequest};
+
+const QUEUE_FILE: &str = "download-queue.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QueueStatus {
+    Pending,
+    Running,
+    Paused,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueuedDownload {
+    pub id: String,
+    pub status: QueueStatus,
+    pub enqueued_at: u64,
+    pub request: DownloadRequest,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct QueueFile {
+    jobs: Vec<QueuedDownload>,
+}
+
+/// Downloads waiting for a free slot, persisted so pending jobs survive a restart.
+pub struct DownloadQueue {
+    jobs: Mutex<Vec<QueuedDownload>>,
+    storage_path: Option<PathBuf>,
+    revision: AtomicU64,
+    saved_revision: Arc<Mutex<u64>>,
+}
+
+impl DownloadQueue {
+    pub fn load() -> Self {
+        let storage_path = match storage::data_file_path(QUEUE_FILE) {
+            Ok(path) => Some(path),
+            Err(err) => {
+                eprintln!("{err}");
+                None
+            }
+        };
+
+        let mut jobs = storage_path
+            .as_deref()
+            .map(|path| {
+                storage::load_json::<QueueFile>(path).unwrap_or_else(|err| {
+                    eprintln!("{err}");
+                    None
+                })
+            })
+            .unwrap_or_default()
+            .unwrap_or_default()
+            .jobs;
+
+        // Anything that was running when the app exited starts over from the queue.
+        for job in &mut jobs {
+            if job.status == QueueStatus::Running {
+                job.status = QueueStatus::Pending;
+            }
+        }
+
+        Self {
+            jobs: Mutex::new(jobs),
+            storage_path,
+            revision: AtomicU64::new(0),
+            saved_revision: Arc::new(Mutex::new(0)),
+        }
+    }
+
+    pub fn snapshot(&self) -> Vec<QueuedDownload> {
+        self.jobs
+            .lock()
+            .map(|jobs| jobs.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn enqueue(&self, id: String, request: DownloadRequest) -> Result<QueuedDownload, String> {
+        self.update(|jobs| {
+            if jobs.iter().any(|job| job.id == id) {
+                return Err("该下载任务已在队列中".into());
+            }
+
+            let job = QueuedDownload {
+                id,
+                status: QueueStatus::Pending,
+                enqueued_at: now_millis(),
+                request,
+            };
+            jobs.push(job.clone());
+            Ok(job)
+        })
+    }
+
+    pub fn remove(&self, id: &str) -> Result<QueuedDownload, String> {
+        self.update(|jobs| {
+            let index = jobs
+                .iter()
+                .position(|job| job.id == id)
+                .ok_or_else(|| "队列中未找到该下载任务".to_string())?;
+            Ok(jobs.remove(index))
+        })
+    }
+
+    /// Moves the listed jobs to the front in the given order; unlisted jobs keep
+    /// their relative order behind them.
+    pub fn reorder(&self, ids: &[String]) -> Result<(), String> {
+        self.update(|jobs| {
+            let mut reordered = Vec::with_capacity(jobs.len());
+            for id in ids {
+                if let Some(index) = jobs.iter().position(|job| &job.id == id) {
+                    reordered.push(jobs.remove(index));
+                }
+            }
+            reordered.append(jobs);
+            *jobs = reordered;
+            Ok(())
+        })
+    }
+
+    /// Marks pending jobs as running until `max_parallel` jobs are active and
+    /// returns the ones that should be started now.
+    pub fn start_ready(&self, max_parallel: usize) -> Vec<QueuedDownload> {
+        self.update(|jobs| {
+            let running = jobs
+                .iter()
+                .filter(|job| job.status == QueueStatus::Running)
+                .count();
+            let mut started = Vec::new();
+
+            for job in jobs.iter_mut() {
+                if running + started.len() >= max_parallel {
+                    break;
+                }
+                if job.status == QueueStatus::Pending {
+                    job.status = QueueStatus::Running;
+                    started.push(job.clone());
+                }
+            }
+
+            Ok(started)
+        })
+        .unwrap_or_default()
+    }
+
+    /// Keeps a stopped download around so it can be resumed later, adding it to
+    /// the queue when it was started directly rather than from the queue.
+    pub fn mark_paused(&self, id: &str, request: DownloadRequest) {
+        let _ = self.update(|jobs| {
+            if let Some(job) = jobs.iter_mut().find(|job| job.id == id) {
+                job.status = QueueStatus::Paused;
+            } else {
+                jobs.push(QueuedDownload {
+                    id: id.to_string(),
+                    status: QueueStatus::Paused,
+                    enqueued_at: now_millis(),
+                    request,
+                });
+            }
+            Ok(())
+        });
+    }
+
+    /// Puts a paused job back at the front of the queue.
+    pub fn resume(&self, id: &str) -> Result<QueuedDownload, String> {
+        self.update(|jobs| {
+            let index = jobs
+                .iter()
+                .position(|job| job.id == id)
+                .ok_or_else(|| "未找到已暂停的下载任务".to_string())?;
+
+            if jobs[index].status != QueueStatus::Paused {
+                return Err("该下载任务未处于暂停状态".into());
+            }
+
+            let mut job = jobs.remove(index);
+            job.status = QueueStatus::Pending;
+            jobs.insert(0, job.clone());
+            Ok(job)
+        })
+    }
+
+    pub fn finish(&self, id: &str) {
+        let _ = self.update(|jobs| {
+            jobs.retain(|job| job.id != id);
+            Ok(())
+        });
+    }
+
+    fn update<T>(
+        &self,
+        change: impl FnOnce(&mut Vec<QueuedDownload>) -> Result<T, String>,
+    ) -> Result<T, String> {
+        let mut jobs = self
+            .jobs
+            .lock()
+            .map_err(|_| "下载队列状态异常".to_string())?;
+        let result = change(&mut jobs)?;
+
+        if let Some(path) = self.storage_path.clone() {
+            let file = QueueFile { jobs: jobs.clone() };
+            let revision = self.revision.fetch_add(1, Ordering::SeqCst) + 1;
+            drop(jobs);
+            self.persist(path, file, revision);
+        }
+
+        Ok(result)
+    }
+
+    /// Writes the snapshot off the async runtime. Writes can finish out of order,
+    /// so one that lost the race to a newer snapshot is dropped.
+    fn persist(&self, path: PathBuf, file: QueueFile, revision: u64) {
+        let saved_revision = Arc::clone(&self.saved_revision);
+        tauri::async_runtime::spawn_blocking(move || {
+            let Ok(mut saved) = saved_revision.lock() else {
+                return;
+            };
+            if *saved >= revision {
+                return;
+            }
+
+            match storage::save_json(&path, &file) {
+                Ok(()) => *saved = revision,
+                Err(err) => eprintln!("保存下载队列失败: {err}"),
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DownloadQueue, QueueStatus};
+    use crate::DownloadRequest;
+    use serde_json::json;
+    use std::sync::{atomic::AtomicU64, Arc, Mutex};
+
+    fn queue() -> DownloadQueue {
+        DownloadQueue {
+            jobs: Mutex::new(Vec::new()),
+            storage_path: None,
+            revision: AtomicU64::new(0),
+            saved_revision: Arc::new(Mutex::new(0)),
+        }
+    }
+
+    fn request(url: &str) -> DownloadRequest {
+        serde_json::from_value(json!({ "url": url, "mode": "video" })).expect("valid request")
+    }
+
+    fn ids(queue: &DownloadQueue) -> Vec<String> {
+        queue.snapshot().into_iter().map(|job| job.id).collect()
+    }
+
+    #[test]
+    fn starts_jobs_up_to_parallel_limit() {
+        let queue = queue();
+        for id in ["a", "b", "c"] {
+            queue.enqueue(id.into(), request(id)).expect("enqueue");
+        }
+
+        let started = queue.start_ready(2);
+        assert_eq!(started.len(), 2);
+        assert!(queue.start_ready(2).is_empty());
+
+        queue.finish("a");
+        let started = queue.start_ready(2);
+        assert_eq!(started.len(), 1);
+        assert_eq!(started[0].id, "c");
+        assert!(queue
+            .snapshot()
+            .iter()
+            .all(|job| job.status == QueueStatus::Running));
+    }
+
+    #[test]
+    fn reorders_listed_jobs_to_front() {
+        let queue = queue();
+        for id in ["a", "b", "c", "d"] {
+            queue.enqueue(id.into(), request(id)).expect("enqueue");
+        }
+
+        queue
+            .reorder(&["c".into(), "missing".into(), "a".into()])
+            .expect("reorder");
+
+        assert_eq!(ids(&queue), ["c", "a", "b", "d"]);
+    }
+
+    #[test]
+    fn paused_jobs_wait_until_resumed_at_front() {
+        let queue = queue();
+        for id in ["a", "b"] {
+            queue.enqueue(id.into(), request(id)).expect("enqueue");
+        }
+        queue.mark_paused("direct", request("direct"));
+        queue.start_ready(1);
+        queue.mark_paused("a", request("a"));
+
+        let started = queue.start_ready(1);
+        assert_eq!(started.len(), 1);
+        assert_eq!(started[0].id, "b");
+        queue.finish("b");
+        assert!(queue.start_ready(1).is_empty());
+
+        assert!(queue.resume("b").is_err());
+        queue.resume("direct").expect("resume");
+        assert_eq!(ids(&queue), ["direct", "a"]);
+        assert_eq!(queue.start_ready(1)[0].id, "direct");
+    }
+
+    #[test]
+    fn rejects_duplicate_ids_and_unknown_removals() {
+        let queue = queue();
+        queue.enqueue("a".into(), request("a")).expect("enqueue");
+
+        assert!(queue.enqueue("a".into(), request("a")).is_err());
+        assert!(queue.remove("b").is_err());
+        assert_eq!(queue.remove("a").expect("remove").id, "a");
+        assert!(queue.snapshot().is_empty());
+    }
+}
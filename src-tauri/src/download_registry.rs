@@ -0,0 +1,157 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::oneshot;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopRequest {
+    Cancel { cleanup_partials: bool },
+    Pause,
+}
+
+struct RunningSession {
+    token: u64,
+    stop_tx: oneshot::Sender<StopRequest>,
+}
+
+#[derive(Default)]
+struct RegistryState {
+    next_token: u64,
+    sessions: HashMap<String, RunningSession>,
+}
+
+/// Running yt-dlp downloads keyed by session id, so other commands can stop them.
+#[derive(Clone, Default)]
+pub struct DownloadRegistry {
+    state: Arc<Mutex<RegistryState>>,
+}
+
+pub struct SessionHandle {
+    pub stop_rx: oneshot::Receiver<StopRequest>,
+    registry: DownloadRegistry,
+    session_id: String,
+    token: u64,
+}
+
+impl DownloadRegistry {
+    pub fn register(&self, session_id: &str) -> Result<SessionHandle, String> {
+        let mut state = self
+            .state
+            .lock()
+            .map_err(|_| "下载任务状态异常".to_string())?;
+
+        if state.sessions.contains_key(session_id) {
+            return Err("该下载任务已在进行中".into());
+        }
+
+        state.next_token += 1;
+        let token = state.next_token;
+        let (stop_tx, stop_rx) = oneshot::channel();
+        state
+            .sessions
+            .insert(session_id.to_string(), RunningSession { token, stop_tx });
+
+        Ok(SessionHandle {
+            stop_rx,
+            registry: self.clone(),
+            session_id: session_id.to_string(),
+            token,
+        })
+    }
+
+    pub fn request_stop(&self, session_id: &str, request: StopRequest) -> Result<(), String> {
+        let session = self
+            .state
+            .lock()
+            .map_err(|_| "下载任务状态异常".to_string())?
+            .sessions
+            .remove(session_id)
+            .ok_or_else(|| "未找到正在进行的下载任务".to_string())?;
+
+        session
+            .stop_tx
+            .send(request)
+            .map_err(|_| "下载任务已结束".to_string())
+    }
+
+    fn release(&self, session_id: &str, token: u64) {
+        if let Ok(mut state) = self.state.lock() {
+            if state
+                .sessions
+                .get(session_id)
+                .is_some_and(|session| session.token == token)
+            {
+                state.sessions.remove(session_id);
+            }
+        }
+    }
+}
+
+impl Drop for SessionHandle {
+    fn drop(&mut self) {
+        self.registry.release(&self.session_id, self.token);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DownloadRegistry, StopRequest};
+
+    #[test]
+    fn rejects_duplicate_session_ids() {
+        let registry = DownloadRegistry::default();
+        let _handle = registry.register("session-1").expect("first register");
+
+        assert!(registry.register("session-1").is_err());
+    }
+
+    #[test]
+    fn delivers_stop_request_to_running_session() {
+        let registry = DownloadRegistry::default();
+        let mut handle = registry.register("session-1").expect("register");
+
+        registry
+            .request_stop(
+                "session-1",
+                StopRequest::Cancel {
+                    cleanup_partials: true,
+                },
+            )
+            .expect("stop should be delivered");
+
+        assert_eq!(
+            handle.stop_rx.try_recv().ok(),
+            Some(StopRequest::Cancel {
+                cleanup_partials: true
+            })
+        );
+        assert!(registry
+            .request_stop(
+                "session-1",
+                StopRequest::Cancel {
+                    cleanup_partials: true,
+                },
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn stale_handle_does_not_release_newer_session() {
+        let registry = DownloadRegistry::default();
+        let first = registry.register("session-1").expect("register");
+        registry
+            .request_stop(
+                "session-1",
+                StopRequest::Cancel {
+                    cleanup_partials: false,
+                },
+            )
+            .expect("stop");
+
+        let _second = registry.register("session-1").expect("re-register");
+        drop(first);
+
+        assert!(registry.register("session-1").is_err());
+    }
+}
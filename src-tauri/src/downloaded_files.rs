@@ -0,0 +1,207 @@
+use serde::Serialize;
+use std::path::Path;
+
+use crate::yt_dlp_progress::DownloadedItem;
+
+const VIDEO_EXTENSIONS: &[&str] = &["mp4", "mkv", "webm", "mov", "m4v", "avi", "flv", "ts"];
+const AUDIO_EXTENSIONS: &[&str] = &["mp3", "m4a", "aac", "opus", "flac", "wav", "ogg", "oga"];
+const SUBTITLE_EXTENSIONS: &[&str] = &["srt", "vtt", "ass", "ssa", "lrc", "ttml"];
+const THUMBNAIL_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp"];
+/// Leftovers of an unfinished download rather than output files.
+const PARTIAL_EXTENSIONS: &[&str] = &["part", "ytdl", "tmp", "temp"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileKind {
+    Video,
+    Audio,
+    Subtitle,
+    Thumbnail,
+    Other,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadedFile {
+    pub path: String,
+    pub size: Option<u64>,
+    pub kind: FileKind,
+}
+
+/// The final file of every item plus the sidecars yt-dlp wrote next to it
+/// (subtitles, thumbnails, descriptions) under the same file stem. Listing
+/// directories is blocking I/O, so it runs off the async runtime.
+pub async fn collect_files(items: &[DownloadedItem]) -> Vec<DownloadedFile> {
+    let items = items.to_vec();
+    tokio::task::spawn_blocking(move || collect_files_blocking(&items))
+        .await
+        .unwrap_or_else(|err| {
+            eprintln!("收集下载文件失败: {err}");
+            Vec::new()
+        })
+}
+
+fn collect_files_blocking(items: &[DownloadedItem]) -> Vec<DownloadedFile> {
+    let mut files: Vec<DownloadedFile> = Vec::new();
+
+    for item in items {
+        let Some(filepath) = item.filepath.as_deref() else {
+            continue;
+        };
+        let path = Path::new(filepath);
+        let has_video = item.video_codec.as_deref().map(|codec| codec != "none");
+
+        let mut item_files = vec![DownloadedFile {
+            path: filepath.to_string(),
+            size: file_size(path),
+            kind: primary_kind(path, has_video),
+        }];
+        item_files.extend(
+            sidecar_paths(path)
+                .into_iter()
+                .map(|sidecar| DownloadedFile {
+                    size: file_size(Path::new(&sidecar)),
+                    kind: sidecar_kind(Path::new(&sidecar)),
+                    path: sidecar,
+                }),
+        );
+
+        for file in item_files {
+            if !files.iter().any(|existing| existing.path == file.path) {
+                files.push(file);
+            }
+        }
+    }
+
+    files
+}
+
+fn primary_kind(path: &Path, has_video: Option<bool>) -> FileKind {
+    match has_video {
+        Some(true) => FileKind::Video,
+        Some(false) => FileKind::Audio,
+        None => match sidecar_kind(path) {
+            FileKind::Other => FileKind::Video,
+            kind => kind,
+        },
+    }
+}
+
+fn sidecar_kind(path: &Path) -> FileKind {
+    let extension = lowercase_extension(path);
+    let extension = extension.as_str();
+    if VIDEO_EXTENSIONS.contains(&extension) {
+        FileKind::Video
+    } else if AUDIO_EXTENSIONS.contains(&extension) {
+        FileKind::Audio
+    } else if SUBTITLE_EXTENSIONS.contains(&extension) {
+        FileKind::Subtitle
+    } else if THUMBNAIL_EXTENSIONS.contains(&extension) {
+        FileKind::Thumbnail
+    } else {
+        FileKind::Other
+    }
+}
+
+/// Files in the same directory named `<stem>.<anything>`, e.g.
+/// `Title [id].en.srt` or `Title [id].webp` next to `Title [id].mp4`.
+fn sidecar_paths(path: &Path) -> Vec<String> {
+    let (Some(parent), Some(stem)) = (path.parent(), path.file_stem()) else {
+        return Vec::new();
+    };
+    let prefix = format!("{}.", stem.to_string_lossy());
+    let Ok(entries) = std::fs::read_dir(parent) else {
+        return Vec::new();
+    };
+
+    let mut sidecars: Vec<String> = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|candidate| candidate != path && candidate.is_file())
+        .filter(|candidate| {
+            candidate
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with(&prefix))
+        })
+        .filter(|candidate| {
+            let extension = lowercase_extension(candidate);
+            !PARTIAL_EXTENSIONS.contains(&extension.as_str())
+        })
+        .map(|candidate| candidate.to_string_lossy().to_string())
+        .collect();
+    sidecars.sort();
+    sidecars
+}
+
+fn lowercase_extension(path: &Path) -> String {
+    path.extension()
+        .map(|extension| extension.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default()
+}
+
+fn file_size(path: &Path) -> Option<u64> {
+    std::fs::metadata(path).ok().map(|metadata| metadata.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{collect_files, FileKind};
+    use crate::yt_dlp_progress::DownloadedItem;
+
+    #[tokio::test]
+    async fn collects_primary_files_and_sidecars_with_kinds() {
+        let dir = std::env::temp_dir().join(format!("yt-dlp-x-files-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).expect("create dir");
+        for (name, content) in [
+            ("Clip [a1].mp4", "video"),
+            ("Clip [a1].en.srt", "subs"),
+            ("Clip [a1].webp", "thumb"),
+            ("Clip [a1].description", "text"),
+            ("Clip [a1].f137.mp4.part", "partial"),
+            ("Clip [a10].mp4", "another video"),
+            ("Song [b2].opus", "audio"),
+        ] {
+            std::fs::write(dir.join(name), content).expect("write");
+        }
+
+        let item = |name: &str, video_codec: Option<&str>| DownloadedItem {
+            filepath: Some(dir.join(name).to_string_lossy().to_string()),
+            video_codec: video_codec.map(str::to_string),
+            ..DownloadedItem::default()
+        };
+        let files = collect_files(&[
+            item("Clip [a1].mp4", Some("avc1")),
+            item("Song [b2].opus", Some("none")),
+            item("Clip [a1].mp4", None),
+        ])
+        .await;
+
+        let summary: Vec<(String, Option<u64>, FileKind)> = files
+            .iter()
+            .map(|file| {
+                let name = std::path::Path::new(&file.path)
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                (name, file.size, file.kind)
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("Clip [a1].mp4".to_string(), Some(5), FileKind::Video),
+                (
+                    "Clip [a1].description".to_string(),
+                    Some(4),
+                    FileKind::Other
+                ),
+                ("Clip [a1].en.srt".to_string(), Some(4), FileKind::Subtitle),
+                ("Clip [a1].webp".to_string(), Some(5), FileKind::Thumbnail),
+                ("Song [b2].opus".to_string(), Some(5), FileKind::Audio),
+            ]
+        );
+
+        std::fs::remove_dir_all(&dir).expect("cleanup");
+    }
+}
@@ -0,0 +1,119 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use crate::utils::http_download::DownloadControl;
+
+/// The binary an install downloads, as named in `install-progress` events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum InstallTarget {
+    YtDlp,
+    Ffmpeg,
+}
+
+#[derive(Default)]
+struct RegistryState {
+    next_token: u64,
+    installs: HashMap<InstallTarget, Vec<(u64, DownloadControl)>>,
+}
+
+/// Running binary installs, so the window can cancel them.
+#[derive(Clone, Default)]
+pub struct InstallRegistry {
+    state: Arc<Mutex<RegistryState>>,
+}
+
+/// Keeps an install registered until dropped.
+pub struct InstallHandle {
+    pub control: DownloadControl,
+    registry: InstallRegistry,
+    target: InstallTarget,
+    token: u64,
+}
+
+impl InstallRegistry {
+    pub fn register(&self, target: InstallTarget, control: DownloadControl) -> InstallHandle {
+        let mut state = self
+            .state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        state.next_token += 1;
+        let token = state.next_token;
+        state
+            .installs
+            .entry(target)
+            .or_default()
+            .push((token, control.clone()));
+
+        InstallHandle {
+            control,
+            registry: self.clone(),
+            target,
+            token,
+        }
+    }
+
+    /// Cancels every running install of `target`, including one still waiting
+    /// for another install of the same binary to finish.
+    pub fn cancel(&self, target: InstallTarget) -> Result<(), String> {
+        let state = self
+            .state
+            .lock()
+            .map_err(|_| "安装任务状态异常".to_string())?;
+        let installs = state
+            .installs
+            .get(&target)
+            .filter(|installs| !installs.is_empty())
+            .ok_or_else(|| "未找到正在进行的安装任务".to_string())?;
+
+        for (_, control) in installs {
+            control.cancel();
+        }
+        Ok(())
+    }
+
+    fn release(&self, target: InstallTarget, token: u64) {
+        if let Ok(mut state) = self.state.lock() {
+            if let Some(installs) = state.installs.get_mut(&target) {
+                installs.retain(|(existing, _)| *existing != token);
+            }
+        }
+    }
+}
+
+impl Drop for InstallHandle {
+    fn drop(&mut self) {
+        self.registry.release(self.target, self.token);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{InstallRegistry, InstallTarget};
+    use crate::utils::http_download::DownloadControl;
+
+    #[test]
+    fn cancels_running_installs_of_a_target() {
+        let registry = InstallRegistry::default();
+        let first = registry.register(InstallTarget::YtDlp, DownloadControl::default());
+        let second = registry.register(InstallTarget::YtDlp, DownloadControl::default());
+        let ffmpeg = registry.register(InstallTarget::Ffmpeg, DownloadControl::default());
+
+        registry.cancel(InstallTarget::YtDlp).expect("cancel");
+
+        assert!(first.control.is_cancelled());
+        assert!(second.control.is_cancelled());
+        assert!(!ffmpeg.control.is_cancelled());
+    }
+
+    #[test]
+    fn finished_installs_can_no_longer_be_cancelled() {
+        let registry = InstallRegistry::default();
+        drop(registry.register(InstallTarget::Ffmpeg, DownloadControl::default()));
+
+        assert!(registry.cancel(InstallTarget::Ffmpeg).is_err());
+    }
+}
@@ -1,16 +1,40 @@
+mod download_archive;
+mod download_history;
+mod download_queue;
+mod download_registry;
+mod downloaded_files;
+mod install_registry;
+mod settings;
+mod sponsorblock;
+#[cfg(test)]
+mod test_support;
 mod utils;
 mod yt_dlp_args;
+mod yt_dlp_errors;
+mod yt_dlp_probe;
 mod yt_dlp_progress;
-
+mod yt_dlp_sections;
+
+use download_archive::{ArchiveScope, DuplicateCheck};
+use download_history::{DownloadHistory, FinishedSession, HistoryPage};
+use download_queue::{DownloadQueue, QueueStatus, QueuedDownload};
+use download_registry::{DownloadRegistry, SessionHandle, StopRequest};
+use downloaded_files::DownloadedFile;
+use install_registry::{InstallHandle, InstallRegistry, InstallTarget};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use settings::AppSettings;
+use sha2::{Digest, Sha256};
 use std::{
     path::{Path, PathBuf},
     process::Stdio,
-    sync::Arc,
-    time::{SystemTime, UNIX_EPOCH},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
-use tauri::{Emitter, Window};
+use tauri::{AppHandle, Emitter, Manager, State};
 use tokio::{
     fs,
     io::{AsyncBufReadExt, BufReader},
@@ -18,13 +42,26 @@ use tokio::{
     sync::Mutex,
 };
 use utils::{
-    ffmpeg::{self, BinarySource as FfmpegBinarySource},
+    ffmpeg::{self, BinarySource as FfmpegBinarySource, FfmpegBinaries},
+    ffmpeg_features::{self, FfmpegFeatures},
+    http_download::{DownloadControl, TransferProgress},
+    process,
     yt_dlp::{self, BinarySource as YtDlpBinarySource},
+    yt_dlp_release::{self, ReleaseChannel, ReleaseEndpoints, UpdateCheck, YtDlpVersion},
+    yt_dlp_store::InstalledYtDlp,
 };
 use yt_dlp_args::{
-    build_yt_dlp_args, BuildYtDlpArgsInput, DownloadModeArg, DownloadTuning, VideoQualityArg,
+    build_yt_dlp_args, ffmpeg_requirements, AudioOptions, BuildYtDlpArgsInput, DownloadModeArg,
+    DownloadTuning, FormatSelection, PlaylistOptions, SponsorBlockOptions, SubtitleOptions,
+    VideoEmbedOptions, VideoOutputOptions, VideoQualityArg,
+};
+use yt_dlp_errors::{classify_stderr, ClassifiedError};
+use yt_dlp_probe::{AvailableSubtitles, MediaInfo};
+use yt_dlp_progress::{
+    parse_downloaded_item_line, parse_playlist_item_line, parse_progress_line, DownloadedItem,
+    PlaylistItemInfo,
 };
-use yt_dlp_progress::parse_progress_line;
+use yt_dlp_sections::SectionOptions;
 
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -40,10 +77,16 @@ struct YtDlpStatus {
 struct FfmpegStatus {
     installed: bool,
     path: Option<String>,
+    ffprobe_path: Option<String>,
     source: Option<String>,
+    /// Version, build configuration, encoders and muxers of the found ffmpeg.
+    #[serde(flatten)]
+    features: FfmpegFeatures,
+    /// Set when ffmpeg was found without the ffprobe yt-dlp also relies on.
+    warning: Option<String>,
 }
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 struct DownloadRequest {
     url: String,
@@ -59,38 +102,74 @@ struct DownloadRequest {
     file_access_retries: Option<u32>,
     concurrent_fragments: Option<u32>,
     retry_sleep: Option<String>,
+    #[serde(default)]
+    playlist: Option<PlaylistOptions>,
+    #[serde(default)]
+    format: Option<FormatSelection>,
+    #[serde(default)]
+    audio: Option<AudioOptions>,
+    #[serde(default)]
+    video_output: Option<VideoOutputOptions>,
+    #[serde(default)]
+    subtitles: Option<SubtitleOptions>,
+    #[serde(default)]
+    embed: Option<VideoEmbedOptions>,
+    #[serde(default)]
+    sponsorblock: Option<SponsorBlockOptions>,
+    #[serde(default)]
+    sections: Option<SectionOptions>,
+    /// Overrides the archive setting for this download; `off` forces a re-download.
+    #[serde(default)]
+    download_archive: Option<ArchiveScope>,
 }
 
-#[derive(Deserialize, Clone, Copy)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
 #[serde(rename_all = "lowercase")]
 enum DownloadMode {
     Audio,
     Video,
 }
 
-#[derive(Debug, Deserialize, Clone, Copy)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default)]
 #[serde(rename_all = "lowercase")]
 enum VideoQuality {
     Low,
     Medium,
+    #[default]
     Highest,
 }
 
-impl Default for VideoQuality {
-    fn default() -> Self {
-        VideoQuality::Highest
-    }
-}
-
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 struct DownloadResponse {
     success: bool,
+    status: DownloadStatus,
     stdout: String,
     stderr: String,
     output_dir: String,
+    files: Vec<DownloadedFile>,
+    error: Option<ClassifiedError>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum DownloadStatus {
+    Completed,
+    Failed,
+    Cancelled,
+    Paused,
 }
 
+/// Wakes the background update checker before its next scheduled check.
+#[derive(Default)]
+struct UpdateCheckTrigger(tokio::sync::Notify);
+
+/// How often the background checker asks GitHub for a newer yt-dlp.
+const UPDATE_CHECK_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// yt-dlp exits with 101 once `--max-downloads` stops a playlist early.
+const MAX_DOWNLOADS_REACHED_EXIT_CODE: i32 = 101;
+
 #[tauri::command]
 async fn check_yt_dlp() -> Result<YtDlpStatus, String> {
     let status = match yt_dlp::detect_existing()? {
@@ -112,8 +191,9 @@ async fn check_yt_dlp() -> Result<YtDlpStatus, String> {
 }
 
 #[tauri::command]
-async fn install_yt_dlp() -> Result<YtDlpStatus, String> {
-    let path = yt_dlp::install_latest().await?;
+async fn install_yt_dlp(app: AppHandle) -> Result<YtDlpStatus, String> {
+    let install = start_install(&app, InstallTarget::YtDlp);
+    let path = yt_dlp::install(&settings::current().yt_dlp_release(), &install.control).await?;
     Ok(YtDlpStatus {
         installed: true,
         path: Some(path_to_string(&path)),
@@ -122,45 +202,266 @@ async fn install_yt_dlp() -> Result<YtDlpStatus, String> {
     })
 }
 
+#[tauri::command]
+async fn list_installed_yt_dlp() -> Result<Vec<InstalledYtDlp>, String> {
+    yt_dlp::list_installed()
+}
+
+#[tauri::command]
+async fn activate_yt_dlp(version: String) -> Result<YtDlpStatus, String> {
+    let path = yt_dlp::activate(&version).await?;
+    Ok(YtDlpStatus {
+        installed: true,
+        path: Some(path_to_string(&path)),
+        source: Some(yt_dlp_source_label(YtDlpBinarySource::Bundled)),
+        version: yt_dlp::get_version(&path).ok(),
+    })
+}
+
+#[tauri::command]
+async fn check_yt_dlp_update() -> Result<UpdateCheck, String> {
+    let existing = yt_dlp::detect_existing()?;
+    let current = existing
+        .as_ref()
+        .and_then(|(path, _)| yt_dlp::get_version(path).ok());
+    let mut check = yt_dlp_release::check_update(
+        &ReleaseEndpoints::default(),
+        &settings::current().yt_dlp_release(),
+        current.as_deref(),
+    )
+    .await?;
+
+    // `install_yt_dlp` only replaces the bundled binary; a system or custom
+    // one is updated by whoever manages it.
+    if !matches!(existing, Some((_, YtDlpBinarySource::Bundled))) {
+        check.update_available = false;
+    }
+
+    Ok(check)
+}
+
+/// Re-checks for a newer yt-dlp while the setting is on and emits
+/// `yt-dlp-update-available` once per new version. Saving the settings wakes
+/// it early through `UpdateCheckTrigger`.
+fn spawn_update_checker(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let trigger = app.state::<UpdateCheckTrigger>();
+        let mut notified: Option<String> = None;
+        loop {
+            if settings::current().check_yt_dlp_updates {
+                match check_yt_dlp_update().await {
+                    Ok(check)
+                        if check.update_available
+                            && notified.as_deref() != Some(check.latest.as_str()) =>
+                    {
+                        if let Err(err) = app.emit("yt-dlp-update-available", &check) {
+                            eprintln!("Failed to emit update event: {err}");
+                        }
+                        notified = Some(check.latest);
+                    }
+                    Ok(_) => {}
+                    Err(err) => eprintln!("检查 yt-dlp 更新失败: {err}"),
+                }
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(UPDATE_CHECK_INTERVAL) => {}
+                _ = trigger.0.notified() => {}
+            }
+        }
+    });
+}
+
+#[tauri::command]
+async fn list_yt_dlp_versions(
+    channel: Option<ReleaseChannel>,
+) -> Result<Vec<YtDlpVersion>, String> {
+    let channel = channel.unwrap_or_else(|| settings::current().yt_dlp_channel);
+    yt_dlp_release::list_versions(&ReleaseEndpoints::default(), channel).await
+}
+
 #[tauri::command]
 async fn check_ffmpeg() -> Result<FfmpegStatus, String> {
-    let status = match ffmpeg::detect_existing()? {
-        Some((path, source)) => FfmpegStatus {
-            installed: true,
-            path: Some(path_to_string(&path)),
-            source: Some(ffmpeg_source_label(source)),
-        },
-        None => FfmpegStatus {
+    Ok(ffmpeg_status(ffmpeg::detect_existing()?).await)
+}
+
+#[tauri::command]
+async fn install_ffmpeg(app: AppHandle) -> Result<FfmpegStatus, String> {
+    let install = start_install(&app, InstallTarget::Ffmpeg);
+    let binaries = ffmpeg::install_latest(&install.control).await?;
+    Ok(ffmpeg_status(Some(binaries)).await)
+}
+
+async fn ffmpeg_status(binaries: Option<FfmpegBinaries>) -> FfmpegStatus {
+    let Some(binaries) = binaries else {
+        return FfmpegStatus {
             installed: false,
             path: None,
+            ffprobe_path: None,
             source: None,
-        },
+            features: FfmpegFeatures::default(),
+            warning: None,
+        };
     };
 
-    Ok(status)
+    let warning = binaries.ffprobe.is_none().then(|| {
+        "已找到 ffmpeg，但缺少 ffprobe，嵌入封面等后处理可能失败，请重新安装 ffmpeg".to_string()
+    });
+    FfmpegStatus {
+        installed: true,
+        path: Some(path_to_string(&binaries.ffmpeg)),
+        ffprobe_path: binaries.ffprobe.as_deref().map(path_to_string),
+        source: Some(ffmpeg_source_label(binaries.source)),
+        features: ffmpeg_features::detect_features(&binaries.ffmpeg).await,
+        warning,
+    }
 }
 
 #[tauri::command]
-async fn install_ffmpeg() -> Result<FfmpegStatus, String> {
-    let path = ffmpeg::install_latest().await?;
-    Ok(FfmpegStatus {
-        installed: true,
-        path: Some(path_to_string(&path)),
-        source: Some(ffmpeg_source_label(FfmpegBinarySource::Bundled)),
-    })
+async fn cancel_install(
+    registry: State<'_, InstallRegistry>,
+    target: InstallTarget,
+) -> Result<(), String> {
+    registry.cancel(target)
+}
+
+/// Registers an install so `cancel_install` can stop it and forwards its
+/// download progress to the window as `install-progress` events.
+fn start_install(app: &AppHandle, target: InstallTarget) -> InstallHandle {
+    let emitter = app.clone();
+    let control = DownloadControl::with_progress(Arc::new(move |progress: TransferProgress| {
+        let payload = json!({
+            "target": target,
+            "bytes": progress.downloaded,
+            "total": progress.total,
+            "speed": progress.bytes_per_second,
+        });
+        if let Err(err) = emitter.emit("install-progress", payload) {
+            eprintln!("Failed to emit install progress: {err}");
+        }
+    }));
+
+    app.state::<InstallRegistry>().register(target, control)
+}
+
+/// Finds a usable yt-dlp, installing the bundled one on first use with the
+/// same progress events and cancellation as `install_yt_dlp`.
+async fn ensure_yt_dlp(app: &AppHandle) -> Result<(PathBuf, YtDlpBinarySource), String> {
+    if let Some(found) = yt_dlp::find_available().await? {
+        return Ok(found);
+    }
+
+    let install = start_install(app, InstallTarget::YtDlp);
+    let path = yt_dlp::install(&settings::current().yt_dlp_release(), &install.control).await?;
+    Ok((path, YtDlpBinarySource::Bundled))
+}
+
+#[tauri::command]
+async fn probe_url(
+    app: AppHandle,
+    url: String,
+    browser: Option<String>,
+) -> Result<MediaInfo, String> {
+    probe_media(&app, &url, browser.as_deref()).await
+}
+
+#[tauri::command]
+async fn list_subtitles(
+    app: AppHandle,
+    url: String,
+    browser: Option<String>,
+) -> Result<AvailableSubtitles, String> {
+    probe_media(&app, &url, browser.as_deref())
+        .await
+        .map(Into::into)
+}
+
+async fn probe_media(
+    app: &AppHandle,
+    url: &str,
+    browser: Option<&str>,
+) -> Result<MediaInfo, String> {
+    let url = url.trim();
+    if url.is_empty() {
+        return Err("请输入有效的视频链接".into());
+    }
+
+    let (binary_path, _) = ensure_yt_dlp(app).await?;
+    let runtime_caps = yt_dlp::detect_capabilities(&binary_path);
+    yt_dlp_probe::probe(&binary_path, url, browser, &runtime_caps).await
+}
+
+#[tauri::command]
+async fn check_already_downloaded(
+    app: AppHandle,
+    url: String,
+    browser: Option<String>,
+    output_dir: Option<String>,
+    playlist: Option<bool>,
+    archive: Option<ArchiveScope>,
+) -> Result<DuplicateCheck, String> {
+    let url = url.trim();
+    if url.is_empty() {
+        return Err("请输入有效的视频链接".into());
+    }
+
+    let scope = archive.unwrap_or_else(|| settings::current().download_archive);
+    let output_dir = resolve_output_dir(output_dir.as_deref());
+    let archive_path = download_archive::archive_path(scope, &output_dir)?;
+
+    let (binary_path, _) = ensure_yt_dlp(&app).await?;
+    let runtime_caps = yt_dlp::detect_capabilities(&binary_path);
+    download_archive::check(
+        &binary_path,
+        url,
+        browser.as_deref(),
+        playlist.unwrap_or(false),
+        &runtime_caps,
+        archive_path.as_deref(),
+    )
+    .await
+}
+
+#[tauri::command]
+async fn check_sponsorblock_api(api_url: String) -> Result<(), String> {
+    sponsorblock::check_api(&api_url).await
 }
 
 #[tauri::command]
 async fn download_media(
-    window: Window,
+    app: AppHandle,
+    registry: State<'_, DownloadRegistry>,
     request: DownloadRequest,
 ) -> Result<DownloadResponse, String> {
+    run_download(&app, &registry, request).await
+}
+
+async fn run_download(
+    app: &AppHandle,
+    registry: &DownloadRegistry,
+    mut request: DownloadRequest,
+) -> Result<DownloadResponse, String> {
+    request.url = request.url.trim().to_string();
+    if request.url.is_empty() {
+        return Err("请输入有效的视频链接".into());
+    }
+
+    let session_id = request
+        .session_id
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+        .unwrap_or_else(|| generate_session_id("session"));
+    request.session_id = Some(session_id.clone());
+    let original_request = request.clone();
+    let started_at = now_millis();
+
     let DownloadRequest {
         url,
         mode,
         browser,
         output_dir,
-        session_id,
+        session_id: _,
         quality,
         filename_template,
         retries,
@@ -168,48 +469,52 @@ async fn download_media(
         file_access_retries,
         concurrent_fragments,
         retry_sleep,
+        playlist,
+        format,
+        audio,
+        video_output,
+        subtitles,
+        embed,
+        sponsorblock,
+        sections,
+        download_archive,
     } = request;
 
-    let url = url.trim().to_string();
-    if url.is_empty() {
-        return Err("请输入有效的视频链接".into());
-    }
-
-    let session_id = session_id.unwrap_or_else(|| {
-        SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .map(|duration| format!("session-{}", duration.as_millis()))
-            .unwrap_or_else(|_| "session-0".into())
-    });
+    let mut session = registry.register(&session_id)?;
     let session_id = Arc::new(session_id);
 
-    let (binary_path, _) = yt_dlp::ensure_available().await?;
-    let runtime_caps = yt_dlp::detect_capabilities(&binary_path);
+    let (mut binary_path, _) = ensure_yt_dlp(app).await?;
 
-    let output_dir = output_dir
-        .as_deref()
-        .map(str::trim)
-        .filter(|value| !value.is_empty())
-        .map(PathBuf::from)
-        .unwrap_or_else(yt_dlp::default_download_dir);
+    let output_dir = resolve_output_dir(output_dir.as_deref());
 
     fs::create_dir_all(&output_dir)
         .await
         .map_err(|err| format!("无法创建下载目录: {err}"))?;
 
-    let temp_dir = output_dir.join(".yt-dlp-temp");
-    if runtime_caps.supports_paths_temp {
-        fs::create_dir_all(&temp_dir)
-            .await
-            .map_err(|err| format!("无法创建临时目录: {err}"))?;
-    }
-
-    let ffmpeg_path = match mode {
-        DownloadMode::Audio => {
-            let (path, _) = ffmpeg::ensure_available()?;
-            Some(path)
-        }
-        DownloadMode::Video => ffmpeg::detect_existing()?.map(|(path, _)| path),
+    let archive_scope = download_archive.unwrap_or_else(|| settings::current().download_archive);
+    let archive_path = download_archive::archive_path(archive_scope, &output_dir)?;
+
+    // Subtitle conversion, embedding, SponsorBlock, sections, remuxing and
+    // recoding all run through ffmpeg, so those video downloads need it installed.
+    let needs_ffmpeg = matches!(mode, DownloadMode::Audio)
+        || subtitles.is_some()
+        || video_output
+            .as_ref()
+            .is_some_and(VideoOutputOptions::requires_ffmpeg)
+        || embed
+            .as_ref()
+            .is_some_and(VideoEmbedOptions::requires_ffmpeg)
+        || sponsorblock
+            .as_ref()
+            .is_some_and(SponsorBlockOptions::requires_ffmpeg)
+        || sections
+            .as_ref()
+            .is_some_and(SectionOptions::requires_ffmpeg);
+
+    let ffmpeg_path = if needs_ffmpeg {
+        Some(ffmpeg::ensure_available()?.ffmpeg)
+    } else {
+        ffmpeg::detect_existing()?.map(|binaries| binaries.ffmpeg)
     };
 
     let mode_arg = match mode {
@@ -217,6 +522,24 @@ async fn download_media(
         DownloadMode::Video => DownloadModeArg::Video,
     };
 
+    // yt-dlp only notices a missing encoder after the download, so say so up front.
+    if let Some(path) = ffmpeg_path.as_deref() {
+        let requirements = ffmpeg_requirements(mode_arg, audio.as_ref(), video_output.as_ref());
+        let features = ffmpeg_features::detect_features(path).await;
+        for warning in features.unmet(&requirements) {
+            if let Err(err) = app.emit(
+                "download-log",
+                json!({
+                    "sessionId": session_id.as_ref(),
+                    "stream": "warning",
+                    "line": warning,
+                }),
+            ) {
+                eprintln!("Failed to emit log event: {err}");
+            }
+        }
+    }
+
     let quality_arg = match quality {
         VideoQuality::Low => VideoQualityArg::Low,
         VideoQuality::Medium => VideoQualityArg::Medium,
@@ -232,23 +555,204 @@ async fn download_media(
         retry_sleep.as_deref(),
     );
 
-    let args = build_yt_dlp_args(BuildYtDlpArgsInput {
-        url: &url,
-        mode: mode_arg,
-        browser: browser.as_deref(),
+    let temp_dir = partials_dir_for(&output_dir, &session_id, &url);
+    let downloaded = Arc::new(Mutex::new(Vec::new()));
+    // A pinned version or custom binary is deliberate, so updating it away would defeat it.
+    let auto_update = {
+        let settings = settings::current();
+        settings.auto_update_yt_dlp
+            && settings.yt_dlp_version.is_none()
+            && settings.yt_dlp_path.is_none()
+    };
+    let mut updated = false;
+
+    let run = loop {
+        let runtime_caps = yt_dlp::detect_capabilities(&binary_path);
+        if runtime_caps.supports_paths_temp {
+            fs::create_dir_all(&temp_dir)
+                .await
+                .map_err(|err| format!("无法创建临时目录: {err}"))?;
+        }
+
+        let args = build_yt_dlp_args(BuildYtDlpArgsInput {
+            url: &url,
+            mode: mode_arg,
+            browser: browser.as_deref(),
+            output_dir: &output_dir,
+            temp_dir: Some(&temp_dir),
+            quality: quality_arg,
+            ffmpeg_path: ffmpeg_path.as_deref(),
+            runtime_caps: &runtime_caps,
+            tuning: tuning.clone(),
+            playlist: playlist.as_ref(),
+            format: format.as_ref(),
+            audio: audio.as_ref(),
+            video_output: video_output.as_ref(),
+            subtitles: subtitles.as_ref(),
+            embed: embed.as_ref(),
+            sponsorblock: sponsorblock.as_ref(),
+            sections: sections.as_ref(),
+            download_archive: archive_path.as_deref(),
+        })?;
+
+        if let Ok(request) = session.stop_rx.try_recv() {
+            return Ok(finish_stopped_download(
+                app,
+                &session_id,
+                request,
+                original_request,
+                &output_dir,
+                String::new(),
+                String::new(),
+            )
+            .await);
+        }
+
+        let run = run_yt_dlp(
+            app,
+            &session_id,
+            &binary_path,
+            &args,
+            &mut session,
+            &downloaded,
+        )
+        .await?;
+
+        // Extractors break whenever a site changes; a newer yt-dlp usually fixes
+        // that, so update once and run the same download again.
+        if auto_update && !updated && run.worth_updating_for() {
+            updated = true;
+            if let Some(path) = update_yt_dlp_for_retry(app, &session_id).await {
+                binary_path = path;
+                continue;
+            }
+        }
+
+        break run;
+    };
+    let YtDlpRun {
+        status,
+        stop_request,
+        stdout,
+        stderr,
+    } = run;
+
+    let items = std::mem::take(&mut *downloaded.lock().await);
+    let history_request = original_request.clone();
+    let finished_session = |status, exit_code| FinishedSession {
+        session_id: &session_id,
+        request: &history_request,
         output_dir: &output_dir,
-        temp_dir: Some(&temp_dir),
-        quality: quality_arg,
-        ffmpeg_path: ffmpeg_path.as_deref(),
-        runtime_caps: &runtime_caps,
-        tuning,
+        started_at,
+        finished_at: now_millis(),
+        status,
+        exit_code,
+    };
+
+    if let Some(request) = stop_request {
+        let mut response = finish_stopped_download(
+            app,
+            &session_id,
+            request,
+            original_request,
+            &output_dir,
+            stdout,
+            stderr,
+        )
+        .await;
+        response.files = downloaded_files::collect_files(&items).await;
+        // Paused sessions come back through the queue and are recorded when they finish.
+        if response.status != DownloadStatus::Paused {
+            app.state::<DownloadHistory>()
+                .record(finished_session(response.status, None), &items)
+                .await;
+        }
+        return Ok(response);
+    }
+
+    let success = status.is_some_and(|status| {
+        status.success()
+            || (status.code() == Some(MAX_DOWNLOADS_REACHED_EXIT_CODE)
+                && playlist
+                    .as_ref()
+                    .is_some_and(|options| options.max_count.is_some()))
     });
+    if success {
+        // Only removes the partials directory when yt-dlp left nothing behind in it.
+        let _ = fs::remove_dir(&temp_dir).await;
+    }
 
-    let mut command = Command::new(&binary_path);
-    command.args(&args);
+    let download_status = if success {
+        DownloadStatus::Completed
+    } else {
+        DownloadStatus::Failed
+    };
+    app.state::<DownloadHistory>()
+        .record(
+            finished_session(download_status, status.and_then(|status| status.code())),
+            &items,
+        )
+        .await;
+
+    let error = if success {
+        None
+    } else {
+        classify_stderr(&stderr)
+    };
+    if let Some(error) = &error {
+        if let Err(err) = app.emit(
+            "download-error",
+            json!({
+                "sessionId": session_id.as_str(),
+                "error": error,
+            }),
+        ) {
+            eprintln!("Failed to emit error event: {err}");
+        }
+    }
+
+    Ok(DownloadResponse {
+        success,
+        status: download_status,
+        stdout,
+        stderr,
+        output_dir: path_to_string(&output_dir),
+        files: downloaded_files::collect_files(&items).await,
+        error,
+    })
+}
+
+struct YtDlpRun {
+    status: Option<std::process::ExitStatus>,
+    stop_request: Option<StopRequest>,
+    stdout: String,
+    stderr: String,
+}
+
+impl YtDlpRun {
+    fn worth_updating_for(&self) -> bool {
+        self.stop_request.is_none()
+            && !self.status.is_some_and(|status| status.success())
+            && classify_stderr(&self.stderr).is_some_and(|error| error.kind.worth_updating_for())
+    }
+}
+
+/// Runs yt-dlp once, streaming its output as events until it exits or the
+/// session is asked to stop.
+async fn run_yt_dlp(
+    app: &AppHandle,
+    session_id: &Arc<String>,
+    binary_path: &Path,
+    args: &[String],
+    session: &mut SessionHandle,
+    downloaded: &Arc<Mutex<Vec<DownloadedItem>>>,
+) -> Result<YtDlpRun, String> {
+    let mut command = Command::new(binary_path);
+    command.args(args);
     command.stdout(Stdio::piped());
     command.stderr(Stdio::piped());
     command.kill_on_drop(true);
+    process::isolate_process_group(&mut command);
 
     let mut child = command
         .spawn()
@@ -256,33 +760,65 @@ async fn download_media(
 
     let stdout_buffer = Arc::new(Mutex::new(Vec::new()));
     let stderr_buffer = Arc::new(Mutex::new(Vec::new()));
+    let playlist_item = Arc::new(Mutex::new(None));
 
     let stdout_task = if let Some(stdout) = child.stdout.take() {
-        let window = window.clone();
-        let session_id = Arc::clone(&session_id);
+        let app = app.clone();
+        let session_id = Arc::clone(session_id);
         let buffer = Arc::clone(&stdout_buffer);
+        let playlist_item = Arc::clone(&playlist_item);
+        let downloaded = Arc::clone(downloaded);
         Some(tokio::spawn(async move {
-            forward_stream(stdout, window, session_id, "stdout", buffer).await
+            forward_stream(
+                stdout,
+                app,
+                session_id,
+                "stdout",
+                buffer,
+                playlist_item,
+                downloaded,
+            )
+            .await
         }))
     } else {
         None
     };
 
     let stderr_task = if let Some(stderr) = child.stderr.take() {
-        let window = window.clone();
-        let session_id = Arc::clone(&session_id);
+        let app = app.clone();
+        let session_id = Arc::clone(session_id);
         let buffer = Arc::clone(&stderr_buffer);
+        let playlist_item = Arc::clone(&playlist_item);
+        let downloaded = Arc::clone(downloaded);
         Some(tokio::spawn(async move {
-            forward_stream(stderr, window, session_id, "stderr", buffer).await
+            forward_stream(
+                stderr,
+                app,
+                session_id,
+                "stderr",
+                buffer,
+                playlist_item,
+                downloaded,
+            )
+            .await
         }))
     } else {
         None
     };
 
-    let status = child
-        .wait()
-        .await
-        .map_err(|err| format!("等待 yt-dlp 结束失败: {err}"))?;
+    let (status, stop_request) = tokio::select! {
+        result = child.wait() => {
+            let status = result.map_err(|err| format!("等待 yt-dlp 结束失败: {err}"))?;
+            (Some(status), None)
+        }
+        Ok(request) = &mut session.stop_rx => (None, Some(request)),
+    };
+
+    if stop_request.is_some() {
+        if let Err(err) = process::terminate_process_tree(&mut child).await {
+            eprintln!("结束 yt-dlp 进程失败: {err}");
+        }
+    }
 
     if let Some(task) = stdout_task {
         match task.await {
@@ -312,21 +848,358 @@ async fn download_media(
     };
     let stderr = stderr.trim().to_string();
 
-    Ok(DownloadResponse {
-        success: status.success(),
+    Ok(YtDlpRun {
+        status,
+        stop_request,
         stdout,
         stderr,
-        output_dir: path_to_string(&output_dir),
     })
 }
 
+/// Installs the latest yt-dlp for a retry, reporting each step as a
+/// `yt-dlp-auto-update` event; `None` when the update failed.
+async fn update_yt_dlp_for_retry(app: &AppHandle, session_id: &str) -> Option<PathBuf> {
+    let emit = |payload: serde_json::Value| {
+        if let Err(err) = app.emit("yt-dlp-auto-update", payload) {
+            eprintln!("Failed to emit auto update event: {err}");
+        }
+    };
+
+    emit(json!({ "sessionId": session_id, "status": "updating" }));
+    let install = start_install(app, InstallTarget::YtDlp);
+    match yt_dlp::install(&settings::current().yt_dlp_release(), &install.control).await {
+        Ok(path) => {
+            emit(json!({
+                "sessionId": session_id,
+                "status": "updated",
+                "version": yt_dlp::get_version(&path).ok(),
+            }));
+            Some(path)
+        }
+        Err(err) => {
+            emit(json!({ "sessionId": session_id, "status": "failed", "error": err }));
+            None
+        }
+    }
+}
+
+async fn finish_stopped_download(
+    app: &AppHandle,
+    session_id: &str,
+    stop: StopRequest,
+    original_request: DownloadRequest,
+    output_dir: &Path,
+    stdout: String,
+    stderr: String,
+) -> DownloadResponse {
+    let status = match stop {
+        StopRequest::Cancel { cleanup_partials } => {
+            if cleanup_partials {
+                remove_partials(output_dir, session_id, &original_request.url).await;
+            }
+            emit_session_status(app, session_id, "cancelled");
+            DownloadStatus::Cancelled
+        }
+        StopRequest::Pause => {
+            app.state::<DownloadQueue>()
+                .mark_paused(session_id, original_request);
+            emit_session_status(app, session_id, "paused");
+            emit_queue_changed(app, &app.state::<DownloadQueue>());
+            DownloadStatus::Paused
+        }
+    };
+
+    DownloadResponse {
+        success: false,
+        status,
+        stdout,
+        stderr,
+        output_dir: path_to_string(output_dir),
+        files: Vec::new(),
+        error: None,
+    }
+}
+
+#[tauri::command]
+async fn cancel_download(
+    app: AppHandle,
+    registry: State<'_, DownloadRegistry>,
+    queue: State<'_, DownloadQueue>,
+    session_id: String,
+    cleanup_partials: Option<bool>,
+) -> Result<(), String> {
+    let session_id = session_id.trim();
+    let cleanup_partials = cleanup_partials.unwrap_or(false);
+
+    let running_err =
+        match registry.request_stop(session_id, StopRequest::Cancel { cleanup_partials }) {
+            Ok(()) => return Ok(()),
+            Err(err) => err,
+        };
+
+    // A paused download has no process left to stop; cancelling it just drops
+    // the saved session and, if asked, its partial files.
+    let paused = queue
+        .snapshot()
+        .into_iter()
+        .find(|job| job.id == session_id && job.status == QueueStatus::Paused)
+        .ok_or(running_err)?;
+
+    queue.remove(&paused.id)?;
+    if cleanup_partials {
+        let output_dir = resolve_output_dir(paused.request.output_dir.as_deref());
+        remove_partials(&output_dir, &paused.id, paused.request.url.trim()).await;
+    }
+
+    emit_session_status(&app, session_id, "cancelled");
+    emit_queue_changed(&app, &queue);
+    Ok(())
+}
+
+#[tauri::command]
+async fn pause_download(
+    registry: State<'_, DownloadRegistry>,
+    session_id: String,
+) -> Result<(), String> {
+    registry.request_stop(session_id.trim(), StopRequest::Pause)
+}
+
+#[tauri::command]
+async fn resume_download(
+    app: AppHandle,
+    queue: State<'_, DownloadQueue>,
+    session_id: String,
+) -> Result<QueuedDownload, String> {
+    let job = queue.resume(session_id.trim())?;
+    emit_session_status(&app, &job.id, "pending");
+    emit_queue_changed(&app, &queue);
+    pump_queue(&app);
+    Ok(job)
+}
+
+#[tauri::command]
+async fn enqueue_download(
+    app: AppHandle,
+    queue: State<'_, DownloadQueue>,
+    mut request: DownloadRequest,
+) -> Result<QueuedDownload, String> {
+    if request.url.trim().is_empty() {
+        return Err("请输入有效的视频链接".into());
+    }
+
+    let id = request
+        .session_id
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+        .unwrap_or_else(|| generate_session_id("queue"));
+    request.session_id = Some(id.clone());
+
+    let job = queue.enqueue(id, request)?;
+    emit_queue_changed(&app, &queue);
+    pump_queue(&app);
+    Ok(job)
+}
+
+#[tauri::command]
+async fn list_queue(queue: State<'_, DownloadQueue>) -> Result<Vec<QueuedDownload>, String> {
+    Ok(queue.snapshot())
+}
+
+#[tauri::command]
+async fn reorder_queue(
+    app: AppHandle,
+    queue: State<'_, DownloadQueue>,
+    ids: Vec<String>,
+) -> Result<Vec<QueuedDownload>, String> {
+    queue.reorder(&ids)?;
+    emit_queue_changed(&app, &queue);
+    Ok(queue.snapshot())
+}
+
+#[tauri::command]
+async fn remove_from_queue(
+    app: AppHandle,
+    queue: State<'_, DownloadQueue>,
+    registry: State<'_, DownloadRegistry>,
+    id: String,
+) -> Result<Vec<QueuedDownload>, String> {
+    let job = queue.remove(id.trim())?;
+    emit_queue_changed(&app, &queue);
+
+    // Pumping now would start another job while the cancelled process is still
+    // shutting down; the task spawned by `pump_queue` pumps once it has exited.
+    if job.status == QueueStatus::Running {
+        let _ = registry.request_stop(
+            &job.id,
+            StopRequest::Cancel {
+                cleanup_partials: false,
+            },
+        );
+    } else {
+        pump_queue(&app);
+    }
+
+    Ok(queue.snapshot())
+}
+
+#[tauri::command]
+async fn list_history(
+    history: State<'_, DownloadHistory>,
+    query: Option<String>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+) -> Result<HistoryPage, String> {
+    history
+        .list(query.as_deref(), offset.unwrap_or(0), limit)
+        .await
+}
+
+#[tauri::command]
+async fn delete_history_entry(
+    history: State<'_, DownloadHistory>,
+    id: String,
+) -> Result<(), String> {
+    history.delete(id.trim()).await
+}
+
+#[tauri::command]
+async fn clear_history(history: State<'_, DownloadHistory>) -> Result<(), String> {
+    history.clear().await
+}
+
+#[tauri::command]
+async fn get_app_settings() -> Result<AppSettings, String> {
+    Ok(settings::current())
+}
+
+#[tauri::command]
+async fn update_app_settings(app: AppHandle, settings: AppSettings) -> Result<AppSettings, String> {
+    let previous = settings::current();
+    // Saving runs custom yt-dlp and ffmpeg binaries to validate them.
+    let saved = tauri::async_runtime::spawn_blocking(move || settings::save(settings))
+        .await
+        .map_err(|err| format!("保存设置失败: {err}"))??;
+    pump_queue(&app);
+    if saved.check_yt_dlp_updates && saved != previous {
+        app.state::<UpdateCheckTrigger>().0.notify_one();
+    }
+    Ok(saved)
+}
+
+/// Starts queued downloads while there are free slots; every finished job pumps
+/// the queue again so the next one takes its place.
+fn pump_queue(app: &AppHandle) {
+    let queue = app.state::<DownloadQueue>();
+    let max_parallel = settings::current().max_parallel_downloads as usize;
+    let started = queue.start_ready(max_parallel);
+    if started.is_empty() {
+        return;
+    }
+
+    emit_queue_changed(app, &queue);
+
+    for job in started {
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move {
+            let registry = app.state::<DownloadRegistry>().inner().clone();
+            let result = run_download(&app, &registry, job.request).await;
+
+            let paused =
+                matches!(&result, Ok(response) if response.status == DownloadStatus::Paused);
+            let payload = match &result {
+                Ok(response) => json!({ "sessionId": job.id, "response": response }),
+                Err(err) => json!({ "sessionId": job.id, "error": err }),
+            };
+            if let Err(err) = app.emit("download-finished", payload) {
+                eprintln!("Failed to emit finished event: {err}");
+            }
+
+            let queue = app.state::<DownloadQueue>();
+            if !paused {
+                queue.finish(&job.id);
+            }
+            emit_queue_changed(&app, &queue);
+            pump_queue(&app);
+        });
+    }
+}
+
+fn emit_queue_changed(app: &AppHandle, queue: &DownloadQueue) {
+    if let Err(err) = app.emit("download-queue", queue.snapshot()) {
+        eprintln!("Failed to emit queue event: {err}");
+    }
+}
+
+fn generate_session_id(prefix: &str) -> String {
+    static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+    let sequence = SEQUENCE.fetch_add(1, Ordering::Relaxed);
+    format!("{prefix}-{}-{sequence}", now_millis())
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+fn resolve_output_dir(output_dir: Option<&str>) -> PathBuf {
+    output_dir
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(yt_dlp::default_download_dir)
+}
+
+async fn remove_partials(output_dir: &Path, session_id: &str, url: &str) {
+    let temp_dir = partials_dir_for(output_dir, session_id, url);
+    if temp_dir.exists() {
+        if let Err(err) = fs::remove_dir_all(&temp_dir).await {
+            eprintln!("清理临时文件失败: {err}");
+        }
+    }
+}
+
+/// Partial downloads live in a per-session folder under `.yt-dlp-temp`. The session
+/// id survives pause and resume, so a resumed job picks its part files back up,
+/// while two jobs for the same link in different modes never share or delete
+/// each other's partials.
+fn partials_dir_for(output_dir: &Path, session_id: &str, url: &str) -> PathBuf {
+    let mut hasher = Sha256::new();
+    hasher.update(session_id.as_bytes());
+    hasher.update(b"\0");
+    hasher.update(url.as_bytes());
+    let digest = hasher.finalize();
+    let key: String = digest
+        .iter()
+        .take(8)
+        .map(|value| format!("{value:02x}"))
+        .collect();
+    output_dir.join(".yt-dlp-temp").join(key)
+}
+
+fn emit_session_status(app: &AppHandle, session_id: &str, status: &str) {
+    if let Err(err) = app.emit(
+        "download-progress",
+        json!({
+            "sessionId": session_id,
+            "status": status,
+        }),
+    ) {
+        eprintln!("Failed to emit progress event: {err}");
+    }
+}
 
 async fn forward_stream<R>(
     reader: R,
-    window: Window,
+    app: AppHandle,
     session_id: Arc<String>,
     stream: &'static str,
     buffer: Arc<Mutex<Vec<String>>>,
+    playlist_item: Arc<Mutex<Option<PlaylistItemInfo>>>,
+    downloaded: Arc<Mutex<Vec<DownloadedItem>>>,
 ) -> Result<(), std::io::Error>
 where
     R: tokio::io::AsyncRead + Unpin,
@@ -338,7 +1211,16 @@ where
             entries.push(line.clone());
         }
 
-        if let Err(err) = window.emit(
+        if let Some(item) = parse_downloaded_item_line(&line) {
+            let mut items = downloaded.lock().await;
+            // A retried run reports the items it skipped as already downloaded again.
+            if !items.contains(&item) {
+                items.push(item);
+            }
+            continue;
+        }
+
+        if let Err(err) = app.emit(
             "download-log",
             json!({
                 "sessionId": session_id.as_ref(),
@@ -349,8 +1231,28 @@ where
             eprintln!("Failed to emit log event: {err}");
         }
 
+        if let Some(item) = parse_playlist_item_line(&line) {
+            *playlist_item.lock().await = Some(item);
+            if let Err(err) = app.emit(
+                "download-progress",
+                json!({
+                    "sessionId": session_id.as_ref(),
+                    "percent": 0.0,
+                    "percentText": "0%",
+                    "status": "downloading",
+                    "playlistIndex": item.index,
+                    "playlistCount": item.total,
+                    "raw": line,
+                }),
+            ) {
+                eprintln!("Failed to emit progress event: {err}");
+            }
+            continue;
+        }
+
         if let Some(progress) = parse_progress_line(&line) {
-            if let Err(err) = window.emit(
+            let item = *playlist_item.lock().await;
+            if let Err(err) = app.emit(
                 "download-progress",
                 json!({
                     "sessionId": session_id.as_ref(),
@@ -361,6 +1263,8 @@ where
                     "total": progress.total,
                     "status": progress.status,
                     "raw": progress.raw,
+                    "playlistIndex": item.map(|item| item.index),
+                    "playlistCount": item.map(|item| item.total),
                 }),
             ) {
                 eprintln!("Failed to emit progress event: {err}");
@@ -408,6 +1312,26 @@ async fn open_directory(path: String) -> Result<(), String> {
     Ok(())
 }
 
+#[tauri::command]
+async fn reveal_file(path: String) -> Result<(), String> {
+    let trimmed = path.trim();
+    if trimmed.is_empty() {
+        return Err("请输入有效的文件路径".into());
+    }
+
+    let canonical_path = expand_user_path(trimmed)?
+        .canonicalize()
+        .map_err(|err| format!("解析路径失败: {err}"))?;
+
+    if !canonical_path.is_file() {
+        return Err("仅支持定位文件路径".into());
+    }
+
+    tauri::async_runtime::spawn_blocking(move || open_in_file_manager(&canonical_path))
+        .await
+        .map_err(|err| format!("打开文件所在目录失败: {err}"))?
+}
+
 fn expand_user_path(input: &str) -> Result<PathBuf, String> {
     if input == "~" {
         user_home_dir().ok_or_else(|| "无法定位用户主目录".to_string())
@@ -427,9 +1351,16 @@ fn user_home_dir() -> Option<PathBuf> {
     directories_next::BaseDirs::new().map(|base| base.home_dir().to_path_buf())
 }
 
+/// Opens directories; files are selected in their folder where the platform
+/// supports it.
 #[cfg(target_os = "macos")]
 fn open_in_file_manager(path: &Path) -> Result<(), String> {
-    let status = std::process::Command::new("open")
+    let mut command = std::process::Command::new("open");
+    if !path.is_dir() {
+        command.arg("-R");
+    }
+
+    let status = command
         .arg(path)
         .status()
         .map_err(|err| format!("执行 open 命令失败: {err}"))?;
@@ -471,10 +1402,17 @@ fn open_in_file_manager(path: &Path) -> Result<(), String> {
     }
 }
 
+/// xdg-open cannot select a file, so files open their containing directory.
 #[cfg(target_os = "linux")]
 fn open_in_file_manager(path: &Path) -> Result<(), String> {
+    let target = if path.is_dir() {
+        path
+    } else {
+        path.parent().unwrap_or(path)
+    };
+
     let status = std::process::Command::new("xdg-open")
-        .arg(path)
+        .arg(target)
         .status()
         .map_err(|err| format!("执行 xdg-open 命令失败: {err}"))?;
 
@@ -508,6 +1446,7 @@ fn yt_dlp_source_label(source: YtDlpBinarySource) -> String {
     match source {
         YtDlpBinarySource::System => "system".into(),
         YtDlpBinarySource::Bundled => "bundled".into(),
+        YtDlpBinarySource::Custom => "custom".into(),
     }
 }
 
@@ -515,6 +1454,7 @@ fn ffmpeg_source_label(source: FfmpegBinarySource) -> String {
     match source {
         FfmpegBinarySource::System => "system".into(),
         FfmpegBinarySource::Bundled => "bundled".into(),
+        FfmpegBinarySource::Custom => "custom".into(),
     }
 }
 
@@ -523,14 +1463,46 @@ pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_opener::init())
+        .manage(DownloadRegistry::default())
+        .manage(InstallRegistry::default())
+        .manage(UpdateCheckTrigger::default())
+        .setup(|app| {
+            app.manage(DownloadQueue::load());
+            app.manage(DownloadHistory::load());
+            pump_queue(app.handle());
+            spawn_update_checker(app.handle().clone());
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             check_yt_dlp,
             check_ffmpeg,
             install_yt_dlp,
+            check_yt_dlp_update,
+            list_yt_dlp_versions,
+            list_installed_yt_dlp,
+            activate_yt_dlp,
             install_ffmpeg,
+            cancel_install,
+            probe_url,
+            list_subtitles,
+            check_already_downloaded,
+            check_sponsorblock_api,
             download_media,
+            cancel_download,
+            pause_download,
+            resume_download,
+            enqueue_download,
+            list_queue,
+            reorder_queue,
+            remove_from_queue,
+            list_history,
+            delete_history_entry,
+            clear_history,
+            get_app_settings,
+            update_app_settings,
             get_default_download_dir,
-            open_directory
+            open_directory,
+            reveal_file
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
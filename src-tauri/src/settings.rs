@@ -0,0 +1,177 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    path::Path,
+    sync::{Mutex, OnceLock},
+};
+
+use crate::{
+    download_archive::ArchiveScope,
+    utils::{
+        ffmpeg, storage, yt_dlp,
+        yt_dlp_release::{self, ReleaseChannel, YtDlpRelease},
+    },
+};
+
+const SETTINGS_FILE: &str = "settings.json";
+const DEFAULT_MAX_PARALLEL_DOWNLOADS: u32 = 2;
+const MAX_PARALLEL_DOWNLOADS_LIMIT: u32 = 8;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AppSettings {
+    pub max_parallel_downloads: u32,
+    /// Default archive for downloads that do not pick one themselves.
+    pub download_archive: ArchiveScope,
+    /// Update yt-dlp and retry once when a download fails because its extractor broke.
+    pub auto_update_yt_dlp: bool,
+    pub yt_dlp_channel: ReleaseChannel,
+    /// Release tag to install instead of the channel's newest build.
+    pub yt_dlp_version: Option<String>,
+    /// Periodically look for a newer yt-dlp in the background.
+    pub check_yt_dlp_updates: bool,
+    /// Run this yt-dlp instead of the bundled or system one.
+    pub yt_dlp_path: Option<String>,
+    /// Run this ffmpeg instead of the bundled or system one.
+    pub ffmpeg_path: Option<String>,
+}
+
+impl Default for AppSettings {
+    fn default() -> Self {
+        Self {
+            max_parallel_downloads: DEFAULT_MAX_PARALLEL_DOWNLOADS,
+            download_archive: ArchiveScope::default(),
+            auto_update_yt_dlp: false,
+            yt_dlp_channel: ReleaseChannel::default(),
+            yt_dlp_version: None,
+            check_yt_dlp_updates: false,
+            yt_dlp_path: None,
+            ffmpeg_path: None,
+        }
+    }
+}
+
+impl AppSettings {
+    pub fn yt_dlp_release(&self) -> YtDlpRelease {
+        YtDlpRelease {
+            channel: self.yt_dlp_channel,
+            tag: self.yt_dlp_version.clone(),
+        }
+    }
+
+    fn sanitized(mut self) -> Self {
+        self.max_parallel_downloads = self
+            .max_parallel_downloads
+            .clamp(1, MAX_PARALLEL_DOWNLOADS_LIMIT);
+        self.yt_dlp_version =
+            yt_dlp_release::normalize_tag(self.yt_dlp_version.as_deref()).unwrap_or_default();
+        self.yt_dlp_path = normalize_path(self.yt_dlp_path);
+        self.ffmpeg_path = normalize_path(self.ffmpeg_path);
+        self
+    }
+
+    /// Custom binaries are only accepted when they actually run.
+    fn validate_binary_paths(&self) -> Result<(), String> {
+        if let Some(path) = self.yt_dlp_path.as_deref() {
+            yt_dlp::get_version(Path::new(path))
+                .map_err(|err| format!("自定义 yt-dlp 路径无效: {err}"))?;
+        }
+
+        if let Some(path) = self.ffmpeg_path.as_deref() {
+            ffmpeg::validate_binary(Path::new(path))
+                .map_err(|err| format!("自定义 ffmpeg 路径无效: {err}"))?;
+        }
+
+        Ok(())
+    }
+}
+
+pub fn current() -> AppSettings {
+    if let Some(settings) = settings_cache().lock().ok().and_then(|cache| cache.clone()) {
+        return settings;
+    }
+
+    let settings = load_from_disk().unwrap_or_else(|err| {
+        eprintln!("{err}");
+        AppSettings::default()
+    });
+
+    if let Ok(mut cache) = settings_cache().lock() {
+        *cache = Some(settings.clone());
+    }
+
+    settings
+}
+
+pub fn save(settings: AppSettings) -> Result<AppSettings, String> {
+    yt_dlp_release::normalize_tag(settings.yt_dlp_version.as_deref())?;
+    let settings = settings.sanitized();
+    settings.validate_binary_paths()?;
+    storage::save_json(&storage::data_file_path(SETTINGS_FILE)?, &settings)?;
+
+    if let Ok(mut cache) = settings_cache().lock() {
+        *cache = Some(settings.clone());
+    }
+
+    Ok(settings)
+}
+
+fn load_from_disk() -> Result<AppSettings, String> {
+    let path = storage::data_file_path(SETTINGS_FILE)?;
+    Ok(storage::load_json::<AppSettings>(&path)?
+        .unwrap_or_default()
+        .sanitized())
+}
+
+/// Trims a user supplied path; an empty one means "not set".
+fn normalize_path(path: Option<String>) -> Option<String> {
+    path.map(|path| path.trim().to_string())
+        .filter(|path| !path.is_empty())
+}
+
+fn settings_cache() -> &'static Mutex<Option<AppSettings>> {
+    static CACHE: OnceLock<Mutex<Option<AppSettings>>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(None))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AppSettings;
+
+    #[test]
+    fn fills_missing_fields_with_defaults() {
+        let settings: AppSettings = serde_json::from_str("{}").expect("should parse");
+        assert_eq!(settings, AppSettings::default());
+    }
+
+    #[test]
+    fn clamps_parallel_download_limit() {
+        let settings = AppSettings {
+            max_parallel_downloads: 0,
+            ..AppSettings::default()
+        }
+        .sanitized();
+        assert_eq!(settings.max_parallel_downloads, 1);
+
+        let settings = AppSettings {
+            max_parallel_downloads: 100,
+            ..AppSettings::default()
+        }
+        .sanitized();
+        assert_eq!(settings.max_parallel_downloads, 8);
+    }
+
+    #[test]
+    fn clears_blank_binary_paths() {
+        let settings = AppSettings {
+            yt_dlp_path: Some("  ".into()),
+            ffmpeg_path: Some(" /opt/ffmpeg/bin/ffmpeg ".into()),
+            ..AppSettings::default()
+        }
+        .sanitized();
+        assert_eq!(settings.yt_dlp_path, None);
+        assert_eq!(
+            settings.ffmpeg_path.as_deref(),
+            Some("/opt/ffmpeg/bin/ffmpeg")
+        );
+    }
+}
@@ -0,0 +1,78 @@
+use std::time::Duration;
+
+const STATUS_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Confirms a SponsorBlock API server answers before it is saved as the
+/// custom `--sponsorblock-api`; yt-dlp itself only warns when it is unreachable.
+pub async fn check_api(api_url: &str) -> Result<(), String> {
+    let base_url = normalize_api_url(api_url)?;
+    let response = reqwest::Client::new()
+        .get(format!("{base_url}/api/status"))
+        .timeout(STATUS_TIMEOUT)
+        .send()
+        .await
+        .map_err(|err| format!("无法连接 SponsorBlock 服务: {err}"))?;
+
+    if !response.status().is_success() {
+        return Err(format!(
+            "SponsorBlock 服务返回异常状态码: {}",
+            response.status()
+        ));
+    }
+
+    Ok(())
+}
+
+pub fn normalize_api_url(api_url: &str) -> Result<String, String> {
+    let trimmed = api_url.trim().trim_end_matches('/');
+    let valid = (trimmed.starts_with("https://") || trimmed.starts_with("http://"))
+        && !trimmed.chars().any(char::is_whitespace)
+        && trimmed
+            .split("://")
+            .nth(1)
+            .is_some_and(|host| !host.is_empty());
+
+    if valid {
+        Ok(trimmed.to_string())
+    } else {
+        Err(format!("无效的 SponsorBlock 服务地址: {api_url}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_api, normalize_api_url};
+    use crate::test_support::{Route, TestServer};
+
+    #[tokio::test]
+    async fn accepts_server_that_answers_status_endpoint() {
+        let server = TestServer::start(vec![Route::ok("/api/status", r#"{"uptime":42}"#)]);
+
+        check_api(&server.base_url)
+            .await
+            .expect("status should pass");
+        check_api(&format!("{}/", server.base_url))
+            .await
+            .expect("trailing slash should be ignored");
+    }
+
+    #[tokio::test]
+    async fn rejects_server_without_status_endpoint() {
+        let server = TestServer::start(Vec::new());
+
+        assert!(check_api(&server.base_url).await.is_err());
+    }
+
+    #[test]
+    fn rejects_non_http_api_urls() {
+        for url in [
+            "",
+            "sponsor.ajay.app",
+            "ftp://sponsor.ajay.app",
+            "https://",
+            "https://a b",
+        ] {
+            assert!(normalize_api_url(url).is_err(), "{url} should be rejected");
+        }
+    }
+}
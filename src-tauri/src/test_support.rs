@@ -0,0 +1,126 @@
+//! A tiny blocking HTTP server that stands in for remote endpoints in tests.
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::TcpListener,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+};
+
+pub struct Route {
+    path: String,
+    status: u16,
+    body: Vec<u8>,
+}
+
+impl Route {
+    /// Sent as the ETag of every route; ranges are only honoured for it.
+    pub const ETAG: &'static str = "\"test\"";
+
+    pub fn ok(path: &str, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            path: path.to_string(),
+            status: 200,
+            body: body.into(),
+        }
+    }
+}
+
+pub struct TestServer {
+    pub base_url: String,
+    ranged_requests: Arc<AtomicUsize>,
+}
+
+impl TestServer {
+    /// Serves `routes` on an ephemeral localhost port until the test process
+    /// exits; unknown paths get a 404. `Range: bytes=N-` requests get a 206
+    /// unless their `If-Range` does not match [`Route::ETAG`].
+    pub fn start(routes: Vec<Route>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind test server");
+        let base_url = format!("http://{}", listener.local_addr().expect("local addr"));
+        let ranged_requests = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&ranged_requests);
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else {
+                    continue;
+                };
+
+                let mut reader = BufReader::new(&mut stream);
+                let mut request_line = String::new();
+                if reader.read_line(&mut request_line).is_err() {
+                    continue;
+                }
+                let mut range_start: Option<usize> = None;
+                let mut if_range: Option<String> = None;
+                let mut header = String::new();
+                while reader.read_line(&mut header).is_ok_and(|read| read > 2) {
+                    if let Some((name, value)) = header.trim().split_once(':') {
+                        let value = value.trim();
+                        if name.eq_ignore_ascii_case("range") {
+                            range_start = value
+                                .strip_prefix("bytes=")
+                                .and_then(|range| range.strip_suffix('-'))
+                                .and_then(|start| start.parse().ok());
+                        } else if name.eq_ignore_ascii_case("if-range") {
+                            if_range = Some(value.to_string());
+                        }
+                    }
+                    header.clear();
+                }
+
+                let path = request_line
+                    .split_whitespace()
+                    .nth(1)
+                    .and_then(|target| target.split('?').next())
+                    .unwrap_or_default();
+                let (mut status, mut body) = routes
+                    .iter()
+                    .find(|route| route.path == path)
+                    .map(|route| (route.status, route.body.as_slice()))
+                    .unwrap_or((404, b"not found".as_slice()));
+
+                let mut content_range = String::new();
+                let same_file = if_range.as_deref().is_none_or(|tag| tag == Route::ETAG);
+                if let (200, Some(start), true) = (status, range_start, same_file) {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    if start < body.len() {
+                        content_range = format!(
+                            "Content-Range: bytes {start}-{}/{}\r\n",
+                            body.len() - 1,
+                            body.len()
+                        );
+                        status = 206;
+                        body = &body[start..];
+                    } else {
+                        content_range = format!("Content-Range: bytes */{}\r\n", body.len());
+                        status = 416;
+                        body = b"";
+                    }
+                }
+
+                let head = format!(
+                    "HTTP/1.1 {status} Test\r\nContent-Length: {}\r\nETag: {}\r\n{content_range}Connection: close\r\n\r\n",
+                    body.len(),
+                    Route::ETAG
+                );
+                let _ = stream.write_all(head.as_bytes());
+                let _ = stream.write_all(body);
+            }
+        });
+
+        Self {
+            base_url,
+            ranged_requests,
+        }
+    }
+
+    /// Requests that asked for a byte range of the current file.
+    pub fn requests_with_range(&self) -> usize {
+        self.ranged_requests.load(Ordering::SeqCst)
+    }
+}
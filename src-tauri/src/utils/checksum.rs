@@ -0,0 +1,141 @@
+use sha2::{Digest, Sha256};
+
+/// Where the expected SHA-256 of a download is published.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChecksumSource {
+    /// A `sha256sum` style listing that covers every asset of a release.
+    Listing { url: String, asset_name: String },
+    /// A file next to the download that holds only its checksum.
+    Sidecar { url: String },
+}
+
+/// Fetches the checksum `source` publishes; `what` names the download in errors.
+pub async fn fetch_expected(
+    client: &reqwest::Client,
+    source: &ChecksumSource,
+    what: &str,
+) -> Result<String, String> {
+    let url = match source {
+        ChecksumSource::Listing { url, .. } | ChecksumSource::Sidecar { url } => url,
+    };
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .map_err(|err| format!("下载 {what} 校验清单失败: {err}"))?;
+
+    if !response.status().is_success() {
+        return Err(format!(
+            "下载 {what} 校验清单失败，状态码: {}",
+            response.status()
+        ));
+    }
+
+    let body = response
+        .bytes()
+        .await
+        .map_err(|err| format!("读取 {what} 校验清单失败: {err}"))?;
+    let body = String::from_utf8_lossy(&body);
+
+    match source {
+        ChecksumSource::Listing { asset_name, .. } => find_release_checksum(&body, asset_name)
+            .ok_or_else(|| format!("未在校验清单中找到 {asset_name} 的校验值")),
+        ChecksumSource::Sidecar { .. } => {
+            parse_sidecar_checksum(&body).ok_or_else(|| format!("{what} 校验文件格式无效"))
+        }
+    }
+}
+
+pub fn find_release_checksum(content: &str, asset_name: &str) -> Option<String> {
+    content.lines().find_map(|line| {
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            return None;
+        }
+
+        let mut parts = trimmed.split_whitespace();
+        let checksum = parts.next()?;
+        let filename = parts.last()?;
+
+        let normalized_name = filename
+            .trim_start_matches('*')
+            .trim_start_matches("./")
+            .trim();
+
+        if normalized_name != asset_name {
+            return None;
+        }
+
+        normalize_checksum(checksum)
+    })
+}
+
+/// Sidecars hold the bare checksum, optionally followed by the file name.
+pub fn parse_sidecar_checksum(content: &str) -> Option<String> {
+    normalize_checksum(content.split_whitespace().next()?)
+}
+
+/// Lowercase hex of everything fed into `hasher`.
+pub fn hex_sha256(hasher: Sha256) -> String {
+    hasher
+        .finalize()
+        .iter()
+        .map(|value| format!("{value:02x}"))
+        .collect()
+}
+
+fn normalize_checksum(checksum: &str) -> Option<String> {
+    if checksum.len() == 64 && checksum.chars().all(|char| char.is_ascii_hexdigit()) {
+        Some(checksum.to_ascii_lowercase())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_release_checksum, hex_sha256, parse_sidecar_checksum};
+    use sha2::{Digest, Sha256};
+
+    #[test]
+    fn parses_checksum_from_sums_file() {
+        let sums = "abc123  yt-dlp\nffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff *yt-dlp.exe\n";
+
+        let value = find_release_checksum(sums, "yt-dlp.exe").expect("checksum should be found");
+
+        assert_eq!(
+            value,
+            "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff"
+        );
+    }
+
+    #[test]
+    fn ignores_invalid_checksum_rows() {
+        let sums = "thisisnothex  yt-dlp\n";
+        let value = find_release_checksum(sums, "yt-dlp");
+        assert!(value.is_none());
+    }
+
+    #[test]
+    fn parses_sidecar_files_with_or_without_a_name() {
+        let checksum = "A".repeat(64);
+        assert_eq!(
+            parse_sidecar_checksum(&format!("{checksum}\n")),
+            Some("a".repeat(64))
+        );
+        assert_eq!(
+            parse_sidecar_checksum(&format!("{checksum} *ffmpeg-release-essentials.zip")),
+            Some("a".repeat(64))
+        );
+        assert_eq!(parse_sidecar_checksum("<html>not found</html>"), None);
+        assert_eq!(parse_sidecar_checksum(""), None);
+    }
+
+    #[test]
+    fn hashes_to_lowercase_hex() {
+        assert_eq!(
+            hex_sha256(Sha256::new_with_prefix(b"abc")),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
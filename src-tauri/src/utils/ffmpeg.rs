@@ -1,32 +1,72 @@
 use directories_next::ProjectDirs;
-use std::path::{Path, PathBuf};
+use std::{
+    io::{Read, Seek},
+    path::{Path, PathBuf},
+};
 use tokio::fs;
 use which::which;
 
-#[derive(Debug, Clone, Copy)]
+use super::{
+    checksum::{self, ChecksumSource},
+    ffmpeg_features,
+    http_download::{self, DownloadControl},
+};
+
+/// Every binary an install provides; yt-dlp needs ffprobe next to ffmpeg for
+/// several postprocessors, such as thumbnail embedding and duration fixups.
+const BINARIES: &[&str] = &["ffmpeg", "ffprobe"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ArchiveFormat {
+    Zip,
+    TarXz,
+}
+
+/// A release archive, the binaries it contains and where its publisher lists
+/// the archive's checksum. Sources without a published SHA-256 are not offered.
+struct ArchiveSource {
+    url: String,
+    checksum: ChecksumSource,
+    format: ArchiveFormat,
+    binaries: &'static [&'static str],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BinarySource {
     System,
     Bundled,
+    /// The path configured in the settings.
+    Custom,
 }
 
-pub fn detect_existing() -> Result<Option<(PathBuf, BinarySource)>, String> {
-    if let Some(path) = detect_bundled_binary()? {
-        return Ok(Some((path, BinarySource::Bundled)));
-    }
+/// An ffmpeg and the ffprobe that belongs to it; `ffprobe` is `None` when
+/// only ffmpeg could be found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FfmpegBinaries {
+    pub ffmpeg: PathBuf,
+    pub ffprobe: Option<PathBuf>,
+    pub source: BinarySource,
+}
 
-    if let Some(path) = detect_system_binary() {
-        return Ok(Some((path, BinarySource::System)));
+/// Prefers a complete pair: a bundled ffmpeg installed before ffprobe was
+/// bundled too only wins when the system has no complete pair either.
+pub fn detect_existing() -> Result<Option<FfmpegBinaries>, String> {
+    if let Some(binaries) = custom_binaries()? {
+        return Ok(Some(binaries));
     }
 
-    Ok(None)
+    Ok(pick_binaries(
+        detect_bundled_binaries()?,
+        detect_system_binaries(),
+    ))
 }
 
-pub fn ensure_available() -> Result<(PathBuf, BinarySource), String> {
+pub fn ensure_available() -> Result<FfmpegBinaries, String> {
     detect_existing()?
         .ok_or_else(|| "未检测到系统或内置 ffmpeg，请先安装后再试，以便下载音频并嵌入封面。".into())
 }
 
-pub async fn install_latest() -> Result<PathBuf, String> {
+pub async fn install_latest(control: &DownloadControl) -> Result<FfmpegBinaries, String> {
     if !cfg!(any(
         target_os = "windows",
         target_os = "macos",
@@ -35,68 +75,168 @@ pub async fn install_latest() -> Result<PathBuf, String> {
         return Err("当前平台暂不支持自动安装 ffmpeg".into());
     }
 
-    let url = download_url()?;
-    let path = bundled_binary_path()?;
+    // A second install would write to the same partial archive.
+    static INSTALL_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+    let _guard = INSTALL_LOCK.lock().await;
+
+    let bin_dir = bundled_bin_dir()?;
+    let sources = download_sources()?;
+
+    // Binaries are staged until every archive is in, so a failed second
+    // download never leaves a new ffmpeg next to an old ffprobe.
+    let staging_dir = bin_dir.join("ffmpeg-staging");
+    let _ = fs::remove_dir_all(&staging_dir).await;
+    let installed = match stage_binaries(sources, &bin_dir, &staging_dir, control).await {
+        Ok(()) => move_staged_binaries(&staging_dir, &bin_dir).await,
+        Err(err) => Err(err),
+    };
+    let _ = fs::remove_dir_all(&staging_dir).await;
+    installed?;
+
+    for name in BINARIES {
+        let path = bin_dir.join(executable_name(name));
+        ensure_executable_permissions(&path).await?;
+        ffmpeg_features::forget(&path);
+    }
+
+    Ok(FfmpegBinaries {
+        ffmpeg: bin_dir.join(executable_name("ffmpeg")),
+        ffprobe: Some(bin_dir.join(executable_name("ffprobe"))),
+        source: BinarySource::Bundled,
+    })
+}
+
+/// Downloads and extracts every archive into `staging_dir`.
+async fn stage_binaries(
+    sources: Vec<ArchiveSource>,
+    bin_dir: &Path,
+    staging_dir: &Path,
+    control: &DownloadControl,
+) -> Result<(), String> {
+    for (index, source) in sources.into_iter().enumerate() {
+        let archive_path = archive_download_path(bin_dir, index, source.format);
+        download_verified_archive(&source, &archive_path, control).await?;
+
+        let target_dir = staging_dir.to_path_buf();
+        let source_path = archive_path.clone();
+        let extracted = tokio::task::spawn_blocking(move || {
+            extract_binaries(&source_path, &target_dir, source.format, source.binaries)
+        })
+        .await
+        .map_err(|err| format!("解压 ffmpeg 压缩包失败: {err}"));
+        // A corrupt archive would fail the same way on resume, so always start over.
+        http_download::remove_partial(&archive_path).await;
+        extracted??;
+    }
+
+    Ok(())
+}
 
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent)
+async fn move_staged_binaries(staging_dir: &Path, bin_dir: &Path) -> Result<(), String> {
+    for name in BINARIES {
+        let name = executable_name(name);
+        fs::rename(staging_dir.join(&name), bin_dir.join(&name))
             .await
-            .map_err(|err| format!("创建目录失败: {err}"))?;
+            .map_err(|err| format!("安装 {name} 失败: {err}"))?;
     }
 
-    let response = reqwest::Client::new()
-        .get(url)
-        .send()
-        .await
-        .map_err(|err| format!("下载 ffmpeg 失败: {err}"))?;
+    Ok(())
+}
 
-    if !response.status().is_success() {
-        return Err(format!("下载 ffmpeg 失败，状态码: {}", response.status()));
+fn pick_binaries(
+    bundled: Option<FfmpegBinaries>,
+    system: Option<FfmpegBinaries>,
+) -> Option<FfmpegBinaries> {
+    let complete = |binaries: &Option<FfmpegBinaries>| {
+        binaries
+            .as_ref()
+            .is_some_and(|binaries| binaries.ffprobe.is_some())
+    };
+
+    if complete(&bundled) || !complete(&system) {
+        bundled.or(system)
+    } else {
+        system
     }
+}
 
-    let bytes = response
-        .bytes()
-        .await
-        .map_err(|err| format!("读取 ffmpeg 下载内容失败: {err}"))?
-        .to_vec();
+/// Returns the ffmpeg version, failing when `path` is not a working ffmpeg.
+pub fn validate_binary(path: &Path) -> Result<String, String> {
+    // A new binary may now live at a path whose features are cached.
+    ffmpeg_features::forget(path);
+    ffmpeg_features::read_version(path)
+}
 
-    let target_path = path.clone();
-    tokio::task::spawn_blocking(move || extract_ffmpeg(bytes, target_path))
-        .await
-        .map_err(|err| format!("解压 ffmpeg 压缩包失败: {err}"))??;
+/// The configured ffmpeg with the ffprobe next to it; like a configured
+/// yt-dlp it never falls back to another install.
+fn custom_binaries() -> Result<Option<FfmpegBinaries>, String> {
+    let Some(ffmpeg) = crate::settings::current().ffmpeg_path.map(PathBuf::from) else {
+        return Ok(None);
+    };
+    if !ffmpeg.is_file() {
+        return Err(format!("自定义 ffmpeg 不存在: {}", ffmpeg.display()));
+    }
+
+    Ok(Some(FfmpegBinaries {
+        ffprobe: sibling_ffprobe(&ffmpeg),
+        ffmpeg,
+        source: BinarySource::Custom,
+    }))
+}
 
-    ensure_executable_permissions(&path).await?;
+fn sibling_ffprobe(ffmpeg: &Path) -> Option<PathBuf> {
+    ffmpeg
+        .parent()
+        .map(|dir| dir.join(executable_name("ffprobe")))
+        .filter(|path| path.is_file())
+}
 
-    Ok(path)
+fn detect_system_binaries() -> Option<FfmpegBinaries> {
+    let ffmpeg = detect_system_binary("ffmpeg")?;
+    // yt-dlp gets `--ffmpeg-location <ffmpeg>` and only looks for ffprobe in
+    // that directory, so an ffprobe elsewhere on PATH would never be used.
+    let ffprobe = sibling_ffprobe(&ffmpeg);
+
+    Some(FfmpegBinaries {
+        ffmpeg,
+        ffprobe,
+        source: BinarySource::System,
+    })
 }
 
-fn detect_system_binary() -> Option<PathBuf> {
-    if let Ok(path) = which("ffmpeg") {
+fn detect_system_binary(name: &str) -> Option<PathBuf> {
+    if let Ok(path) = which(name) {
         return Some(path);
     }
 
     #[cfg(target_os = "windows")]
     {
-        if let Ok(path) = which("ffmpeg.exe") {
+        if let Ok(path) = which(executable_name(name)) {
             return Some(path);
         }
     }
 
-    super::path_search::locate_macos_binary(&["ffmpeg"])
+    super::path_search::locate_macos_binary(&[name])
 }
 
-fn detect_bundled_binary() -> Result<Option<PathBuf>, String> {
-    let path = bundled_binary_path()?;
-    if path.exists() {
-        Ok(Some(path))
-    } else {
-        Ok(None)
+fn detect_bundled_binaries() -> Result<Option<FfmpegBinaries>, String> {
+    let bin_dir = bundled_bin_dir()?;
+    let ffmpeg = bin_dir.join(executable_name("ffmpeg"));
+    if !ffmpeg.exists() {
+        return Ok(None);
     }
+
+    let ffprobe = bin_dir.join(executable_name("ffprobe"));
+    Ok(Some(FfmpegBinaries {
+        ffmpeg,
+        ffprobe: ffprobe.exists().then_some(ffprobe),
+        source: BinarySource::Bundled,
+    }))
 }
 
-fn bundled_binary_path() -> Result<PathBuf, String> {
+fn bundled_bin_dir() -> Result<PathBuf, String> {
     let dirs = project_dirs()?;
-    Ok(dirs.data_dir().join("bin").join(binary_file_name()))
+    Ok(dirs.data_dir().join("bin"))
 }
 
 fn project_dirs() -> Result<ProjectDirs, String> {
@@ -104,48 +244,122 @@ fn project_dirs() -> Result<ProjectDirs, String> {
         .ok_or_else(|| "无法定位应用数据目录".to_string())
 }
 
-fn binary_file_name() -> &'static str {
+fn executable_name(name: &str) -> String {
     if cfg!(target_os = "windows") {
-        "ffmpeg.exe"
+        format!("{name}.exe")
     } else {
-        "ffmpeg"
+        name.to_string()
     }
 }
 
-fn download_url() -> Result<&'static str, String> {
-    if cfg!(target_os = "windows") {
-        Ok("https://github.com/GyanD/codexffmpeg/releases/latest/download/ffmpeg-release-essentials.zip")
-    } else if cfg!(target_os = "macos") {
-        Ok("https://evermeet.cx/ffmpeg/getrelease/zip")
-    } else if cfg!(target_os = "linux") {
-        Ok("https://github.com/BtbN/FFmpeg-Builds/releases/latest/download/ffmpeg-master-latest-linux64-gpl.tar.xz")
-    } else {
-        Err("当前平台暂不支持自动安装 ffmpeg".into())
+fn download_sources() -> Result<Vec<ArchiveSource>, String> {
+    download_sources_for(std::env::consts::OS)
+}
+
+fn download_sources_for(os: &str) -> Result<Vec<ArchiveSource>, String> {
+    match os {
+        "windows" => {
+            // https://www.gyan.dev/ffmpeg/builds/ links a `.sha256` next to each
+            // build. The GitHub mirror has no sidecar, so download from gyan.dev
+            // too to keep the archive and its checksum in step.
+            let url = "https://www.gyan.dev/ffmpeg/builds/ffmpeg-release-essentials.zip";
+            Ok(vec![ArchiveSource {
+                url: url.into(),
+                checksum: ChecksumSource::Sidecar {
+                    url: format!("{url}.sha256"),
+                },
+                format: ArchiveFormat::Zip,
+                binaries: BINARIES,
+            }])
+        }
+        // The common static macOS builds (https://evermeet.cx/ffmpeg/) only
+        // publish GPG signatures, no SHA-256, so an unverified archive would be
+        // installed; ask for a managed install instead.
+        "macos" => Err(
+            "macOS 暂无可校验的 ffmpeg 安装包，请通过 Homebrew 安装 (brew install ffmpeg) 或在设置中指定 ffmpeg 路径"
+                .into(),
+        ),
+        "linux" => {
+            // Every BtbN release carries a `checksums.sha256` listing all assets.
+            let base = "https://github.com/BtbN/FFmpeg-Builds/releases/latest/download";
+            let asset_name = "ffmpeg-master-latest-linux64-gpl.tar.xz";
+            Ok(vec![ArchiveSource {
+                url: format!("{base}/{asset_name}"),
+                checksum: ChecksumSource::Listing {
+                    url: format!("{base}/checksums.sha256"),
+                    asset_name: asset_name.into(),
+                },
+                format: ArchiveFormat::TarXz,
+                binaries: BINARIES,
+            }])
+        }
+        _ => Err("当前平台暂不支持自动安装 ffmpeg".into()),
     }
 }
 
-fn extract_ffmpeg(bytes: Vec<u8>, target_path: PathBuf) -> Result<(), String> {
-    if cfg!(target_os = "windows") {
-        extract_ffmpeg_from_zip(bytes, target_path, "ffmpeg.exe")
-    } else if cfg!(target_os = "macos") {
-        extract_ffmpeg_from_zip(bytes, target_path, "ffmpeg")
-    } else if cfg!(target_os = "linux") {
-        extract_ffmpeg_from_tar_xz(bytes, target_path)
-    } else {
-        Err("当前平台暂不支持自动安装 ffmpeg".into())
+/// Downloads the archive and checks it against the published checksum, so a
+/// tampered or truncated archive never reaches extraction.
+async fn download_verified_archive(
+    source: &ArchiveSource,
+    archive_path: &Path,
+    control: &DownloadControl,
+) -> Result<(), String> {
+    let client = reqwest::Client::new();
+    let expected = checksum::fetch_expected(&client, &source.checksum, "ffmpeg").await?;
+    let downloaded = http_download::download_file(&client, &source.url, archive_path, control)
+        .await
+        .map_err(|err| format!("下载 ffmpeg 失败: {err}"))?;
+
+    if downloaded.sha256 != expected {
+        http_download::remove_partial(archive_path).await;
+        return Err("ffmpeg 下载校验失败，请稍后重试".into());
     }
+
+    Ok(())
+}
+
+/// Release archives are streamed here before extraction, next to the binaries.
+fn archive_download_path(bin_dir: &Path, index: usize, format: ArchiveFormat) -> PathBuf {
+    let extension = match format {
+        ArchiveFormat::Zip => "zip",
+        ArchiveFormat::TarXz => "tar.xz",
+    };
+    bin_dir.join(format!("ffmpeg-download-{index}.{extension}.part"))
 }
 
-fn extract_ffmpeg_from_zip(
-    bytes: Vec<u8>,
-    target_path: PathBuf,
-    binary_name: &str,
+/// Extracts `binaries` from the archive into `target_dir`, failing when the
+/// archive lacks any of them.
+fn extract_binaries(
+    archive_path: &Path,
+    target_dir: &Path,
+    format: ArchiveFormat,
+    binaries: &[&str],
 ) -> Result<(), String> {
-    use std::io::{Cursor};
+    let archive = std::fs::File::open(archive_path)
+        .map_err(|err| format!("读取 ffmpeg 压缩包失败: {err}"))?;
+    let archive = std::io::BufReader::new(archive);
+    std::fs::create_dir_all(target_dir).map_err(|err| format!("创建目录失败: {err}"))?;
+
+    let names: Vec<String> = binaries.iter().map(|name| executable_name(name)).collect();
+    let extracted = match format {
+        ArchiveFormat::Zip => extract_from_zip(archive, target_dir, &names)?,
+        ArchiveFormat::TarXz => extract_from_tar_xz(archive, target_dir, &names)?,
+    };
+
+    match names.iter().find(|name| !extracted.contains(name)) {
+        Some(missing) => Err(format!("未在压缩包中找到 {missing} 可执行文件")),
+        None => Ok(()),
+    }
+}
 
-    let reader = Cursor::new(bytes);
+fn extract_from_zip(
+    reader: impl Read + Seek,
+    target_dir: &Path,
+    names: &[String],
+) -> Result<Vec<String>, String> {
     let mut archive =
         zip::ZipArchive::new(reader).map_err(|err| format!("解析 ffmpeg 压缩包失败: {err}"))?;
+    let mut extracted = Vec::new();
 
     for index in 0..archive.len() {
         let mut file = archive
@@ -156,29 +370,24 @@ fn extract_ffmpeg_from_zip(
             continue;
         }
 
-        let name = file.name().to_string();
-        if name.ends_with(binary_name) {
-            if let Some(parent) = target_path.parent() {
-                std::fs::create_dir_all(parent).map_err(|err| format!("创建目录失败: {err}"))?;
-            }
-
-            let mut output = std::fs::File::create(&target_path)
-                .map_err(|err| format!("写入 ffmpeg 文件失败: {err}"))?;
-            std::io::copy(&mut file, &mut output)
-                .map_err(|err| format!("解压 ffmpeg 文件失败: {err}"))?;
-            return Ok(());
-        }
+        let Some(name) = entry_binary_name(Path::new(file.name()), names) else {
+            continue;
+        };
+        write_binary(&mut file, &target_dir.join(&name))?;
+        extracted.push(name);
     }
 
-    Err("未在压缩包中找到 ffmpeg 可执行文件".into())
+    Ok(extracted)
 }
 
-fn extract_ffmpeg_from_tar_xz(bytes: Vec<u8>, target_path: PathBuf) -> Result<(), String> {
-    use std::io::{Cursor};
-
-    let cursor = Cursor::new(bytes);
-    let decompressor = xz2::read::XzDecoder::new(cursor);
+fn extract_from_tar_xz(
+    reader: impl Read,
+    target_dir: &Path,
+    names: &[String],
+) -> Result<Vec<String>, String> {
+    let decompressor = xz2::read::XzDecoder::new(reader);
     let mut archive = tar::Archive::new(decompressor);
+    let mut extracted = Vec::new();
 
     let entries = archive
         .entries()
@@ -190,23 +399,28 @@ fn extract_ffmpeg_from_tar_xz(bytes: Vec<u8>, target_path: PathBuf) -> Result<()
             .path()
             .map_err(|err| format!("解析压缩包路径失败: {err}"))?;
 
-        if let Some(name) = path.file_name().and_then(|segment| segment.to_str()) {
-            if name == "ffmpeg" {
-                if let Some(parent) = target_path.parent() {
-                    std::fs::create_dir_all(parent)
-                        .map_err(|err| format!("创建目录失败: {err}"))?;
-                }
-
-                let mut output = std::fs::File::create(&target_path)
-                    .map_err(|err| format!("写入 ffmpeg 文件失败: {err}"))?;
-                std::io::copy(&mut entry, &mut output)
-                    .map_err(|err| format!("解压 ffmpeg 文件失败: {err}"))?;
-                return Ok(());
-            }
-        }
+        let Some(name) = entry_binary_name(&path, names) else {
+            continue;
+        };
+        write_binary(&mut entry, &target_dir.join(&name))?;
+        extracted.push(name);
     }
 
-    Err("未在压缩包中找到 ffmpeg 可执行文件".into())
+    Ok(extracted)
+}
+
+/// The wanted binary an archive entry holds, matched on its file name only
+/// since every publisher nests them in differently named directories.
+fn entry_binary_name(path: &Path, names: &[String]) -> Option<String> {
+    let file_name = path.file_name()?.to_str()?;
+    names.iter().find(|name| *name == file_name).cloned()
+}
+
+fn write_binary(source: &mut impl Read, target_path: &Path) -> Result<(), String> {
+    let mut output =
+        std::fs::File::create(target_path).map_err(|err| format!("写入 ffmpeg 文件失败: {err}"))?;
+    std::io::copy(source, &mut output).map_err(|err| format!("解压 ffmpeg 文件失败: {err}"))?;
+    Ok(())
 }
 
 #[cfg(unix)]
@@ -227,3 +441,255 @@ async fn ensure_executable_permissions(path: &Path) -> Result<(), String> {
 async fn ensure_executable_permissions(_path: &Path) -> Result<(), String> {
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        download_sources_for, download_verified_archive, extract_binaries, pick_binaries,
+        stage_binaries, ArchiveFormat, ArchiveSource, BinarySource, FfmpegBinaries, BINARIES,
+    };
+    use crate::{
+        test_support::{Route, TestServer},
+        utils::{
+            checksum::{self, hex_sha256, ChecksumSource},
+            http_download::DownloadControl,
+        },
+    };
+    use sha2::{Digest, Sha256};
+    use std::path::PathBuf;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("yt-dlp-x-ffmpeg-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn tar_xz(entries: &[&str]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(xz2::write::XzEncoder::new(Vec::new(), 1));
+        for path in entries {
+            let content = format!("binary at {path}");
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o755);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, path, content.as_bytes())
+                .expect("append entry");
+        }
+        builder
+            .into_inner()
+            .expect("finish tar")
+            .finish()
+            .expect("finish xz")
+    }
+
+    fn found(source: BinarySource, ffprobe: bool) -> Option<FfmpegBinaries> {
+        Some(FfmpegBinaries {
+            ffmpeg: PathBuf::from("ffmpeg"),
+            ffprobe: ffprobe.then(|| PathBuf::from("ffprobe")),
+            source,
+        })
+    }
+
+    #[test]
+    fn extracts_ffmpeg_and_ffprobe_from_the_archive() {
+        let dir = temp_dir("extract");
+        std::fs::create_dir_all(&dir).expect("create dir");
+        let archive_path = dir.join("archive.tar.xz");
+        std::fs::write(
+            &archive_path,
+            tar_xz(&[
+                "ffmpeg-master-latest-linux64-gpl/bin/ffmpeg",
+                "ffmpeg-master-latest-linux64-gpl/bin/ffplay",
+                "ffmpeg-master-latest-linux64-gpl/bin/ffprobe",
+                "ffmpeg-master-latest-linux64-gpl/doc/ffmpeg.html",
+            ]),
+        )
+        .expect("write archive");
+
+        let target = dir.join("bin");
+        extract_binaries(&archive_path, &target, ArchiveFormat::TarXz, BINARIES)
+            .expect("extract binaries");
+
+        let mut names: Vec<_> = std::fs::read_dir(&target)
+            .expect("read bin dir")
+            .map(|entry| {
+                entry
+                    .expect("entry")
+                    .file_name()
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect();
+        names.sort();
+        assert_eq!(names, ["ffmpeg", "ffprobe"]);
+        assert_eq!(
+            std::fs::read_to_string(target.join("ffprobe")).expect("read ffprobe"),
+            "binary at ffmpeg-master-latest-linux64-gpl/bin/ffprobe"
+        );
+
+        std::fs::write(&archive_path, tar_xz(&["build/bin/ffmpeg"])).expect("write archive");
+        assert_eq!(
+            extract_binaries(&archive_path, &target, ArchiveFormat::TarXz, BINARIES),
+            Err("未在压缩包中找到 ffprobe 可执行文件".to_string())
+        );
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn stages_binaries_until_every_archive_is_in() {
+        let ffmpeg_archive = tar_xz(&["build/bin/ffmpeg"]);
+        let checksum = hex_sha256(Sha256::new_with_prefix(&ffmpeg_archive));
+        let server = TestServer::start(vec![
+            Route::ok("/ffmpeg.tar.xz", ffmpeg_archive),
+            Route::ok("/ffmpeg.tar.xz.sha256", checksum),
+            Route::ok("/ffprobe.tar.xz", tar_xz(&["build/bin/ffprobe"])),
+            Route::ok("/ffprobe.tar.xz.sha256", "0".repeat(64)),
+        ]);
+        let source = |name: &str, binaries: &'static [&'static str]| ArchiveSource {
+            url: format!("{}/{name}.tar.xz", server.base_url),
+            checksum: ChecksumSource::Sidecar {
+                url: format!("{}/{name}.tar.xz.sha256", server.base_url),
+            },
+            format: ArchiveFormat::TarXz,
+            binaries,
+        };
+        let bin_dir = temp_dir("staging");
+        let staging_dir = bin_dir.join("ffmpeg-staging");
+        std::fs::create_dir_all(&bin_dir).expect("create dir");
+
+        let result = stage_binaries(
+            vec![
+                source("ffmpeg", &["ffmpeg"]),
+                source("ffprobe", &["ffprobe"]),
+            ],
+            &bin_dir,
+            &staging_dir,
+            &DownloadControl::default(),
+        )
+        .await;
+
+        assert_eq!(result, Err("ffmpeg 下载校验失败，请稍后重试".to_string()));
+        assert!(staging_dir.join("ffmpeg").exists());
+        assert!(!bin_dir.join("ffmpeg").exists());
+
+        let _ = std::fs::remove_dir_all(&bin_dir);
+    }
+
+    #[test]
+    fn prefers_a_complete_pair() {
+        let pick = |bundled, system| pick_binaries(bundled, system).map(|found| found.source);
+
+        assert_eq!(
+            pick(
+                found(BinarySource::Bundled, true),
+                found(BinarySource::System, true)
+            ),
+            Some(BinarySource::Bundled)
+        );
+        assert_eq!(
+            pick(
+                found(BinarySource::Bundled, false),
+                found(BinarySource::System, true)
+            ),
+            Some(BinarySource::System)
+        );
+        assert_eq!(
+            pick(
+                found(BinarySource::Bundled, false),
+                found(BinarySource::System, false)
+            ),
+            Some(BinarySource::Bundled)
+        );
+        assert_eq!(
+            pick(None, found(BinarySource::System, false)),
+            Some(BinarySource::System)
+        );
+        assert_eq!(pick(None, None), None);
+    }
+
+    #[tokio::test]
+    async fn verifies_archives_before_extraction() {
+        let archive = b"not really an archive".to_vec();
+        let checksum = hex_sha256(Sha256::new_with_prefix(&archive));
+        let server = TestServer::start(vec![
+            Route::ok("/ffmpeg.tar.xz", archive),
+            Route::ok(
+                "/checksums.sha256",
+                format!("{checksum}  ffmpeg.tar.xz\n{}  other.zip\n", "0".repeat(64)),
+            ),
+            Route::ok("/ffmpeg.tar.xz.sha256", "0".repeat(64)),
+        ]);
+        let archive_path = temp_dir("verify").join("ffmpeg-download-0.tar.xz.part");
+        let url = format!("{}/ffmpeg.tar.xz", server.base_url);
+
+        let listed = ArchiveSource {
+            url: url.clone(),
+            checksum: ChecksumSource::Listing {
+                url: format!("{}/checksums.sha256", server.base_url),
+                asset_name: "ffmpeg.tar.xz".into(),
+            },
+            format: ArchiveFormat::TarXz,
+            binaries: BINARIES,
+        };
+        download_verified_archive(&listed, &archive_path, &DownloadControl::default())
+            .await
+            .expect("matching archive should verify");
+        assert!(archive_path.exists());
+
+        let mismatched = ArchiveSource {
+            url,
+            checksum: ChecksumSource::Sidecar {
+                url: format!("{}/ffmpeg.tar.xz.sha256", server.base_url),
+            },
+            format: ArchiveFormat::TarXz,
+            binaries: BINARIES,
+        };
+        let result =
+            download_verified_archive(&mismatched, &archive_path, &DownloadControl::default())
+                .await;
+        assert_eq!(result, Err("ffmpeg 下载校验失败，请稍后重试".to_string()));
+        assert!(!archive_path.exists());
+
+        let _ = std::fs::remove_dir_all(archive_path.parent().expect("parent"));
+    }
+
+    #[test]
+    fn every_platform_source_is_verified_by_its_publisher() {
+        let host = |url: &str| url.split('/').nth(2).map(str::to_string);
+
+        for os in ["windows", "macos", "linux"] {
+            let sources = match download_sources_for(os) {
+                Ok(sources) => sources,
+                Err(err) => {
+                    assert_eq!(os, "macos", "{err}");
+                    assert!(err.contains("brew install ffmpeg"), "{err}");
+                    continue;
+                }
+            };
+            assert!(!sources.is_empty(), "{os}");
+
+            for source in sources {
+                let (ChecksumSource::Listing { url, .. } | ChecksumSource::Sidecar { url }) =
+                    &source.checksum;
+                assert!(url.starts_with("https://"), "{os} {url}");
+                assert_eq!(host(url), host(&source.url), "{os} {url}");
+            }
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs network access to the ffmpeg publishers"]
+    async fn published_checksums_resolve() {
+        let client = reqwest::Client::new();
+        for os in ["windows", "linux"] {
+            for source in download_sources_for(os).expect("supported platform") {
+                checksum::fetch_expected(&client, &source.checksum, "ffmpeg")
+                    .await
+                    .unwrap_or_else(|err| panic!("{os} {}: {err}", source.url));
+            }
+        }
+    }
+}
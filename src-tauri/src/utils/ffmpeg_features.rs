@@ -0,0 +1,353 @@
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{Mutex, OnceLock},
+};
+
+/// What an ffmpeg build reports about itself; empty when it could not be run.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FfmpegFeatures {
+    pub version: Option<String>,
+    /// `./configure` flags the build was made with, e.g. `--enable-libmp3lame`.
+    pub configuration: Vec<String>,
+    pub encoders: Vec<String>,
+    pub muxers: Vec<String>,
+}
+
+/// Something a postprocessing step needs from the ffmpeg build.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FfmpegRequirement {
+    /// Any one of the interchangeable encoders, preferred one first.
+    Encoder {
+        any_of: &'static [&'static str],
+        output: &'static str,
+    },
+    Muxer {
+        name: &'static str,
+        output: &'static str,
+    },
+}
+
+impl FfmpegFeatures {
+    pub fn has_encoder(&self, name: &str) -> bool {
+        self.encoders.iter().any(|encoder| encoder == name)
+    }
+
+    pub fn has_muxer(&self, name: &str) -> bool {
+        self.muxers.iter().any(|muxer| muxer == name)
+    }
+
+    /// A warning per requirement this build cannot meet. Nothing is reported
+    /// for a build that could not be inspected, since that says nothing about
+    /// what it supports.
+    pub fn unmet(&self, requirements: &[FfmpegRequirement]) -> Vec<String> {
+        if self.version.is_none() {
+            return Vec::new();
+        }
+
+        requirements
+            .iter()
+            .filter_map(|requirement| match *requirement {
+                FfmpegRequirement::Encoder { any_of, output }
+                    if !any_of.iter().any(|name| self.has_encoder(name)) =>
+                {
+                    Some(format!(
+                        "当前 ffmpeg 缺少 {} 编码器，可能无法生成 {output}",
+                        any_of.join(" / ")
+                    ))
+                }
+                FfmpegRequirement::Muxer { name, output } if !self.has_muxer(name) => Some(
+                    format!("当前 ffmpeg 不支持 {name} 封装格式，可能无法生成 {output}"),
+                ),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Runs `ffmpeg -version`, `-encoders` and `-muxers` once per binary path, off
+/// the async runtime. A failed run is not cached, so the next call tries again.
+pub async fn detect_features(path: &Path) -> FfmpegFeatures {
+    let key = path.to_path_buf();
+    if let Some(value) = feature_cache()
+        .lock()
+        .ok()
+        .and_then(|cache| cache.get(&key).cloned())
+    {
+        return value;
+    }
+
+    let binary = key.clone();
+    let detected = tokio::task::spawn_blocking(move || detect_features_inner(&binary))
+        .await
+        .map_err(|err| format!("检测 ffmpeg 功能失败: {err}"))
+        .and_then(|result| result);
+    let features = match detected {
+        Ok(features) => features,
+        Err(err) => {
+            eprintln!("{err}");
+            return FfmpegFeatures::default();
+        }
+    };
+
+    if let Ok(mut cache) = feature_cache().lock() {
+        cache.insert(key, features.clone());
+    }
+
+    features
+}
+
+/// Drops the cached features of a binary that was just replaced.
+pub fn forget(path: &Path) {
+    if let Ok(mut cache) = feature_cache().lock() {
+        cache.remove(path);
+    }
+}
+
+/// Runs `ffmpeg -version` uncached, failing when the output is not ffmpeg's.
+pub fn read_version(path: &Path) -> Result<String, String> {
+    let (version, _) = parse_version_output(&run(path, "-version")?);
+    version.ok_or_else(|| "无法识别 ffmpeg 版本信息".to_string())
+}
+
+fn feature_cache() -> &'static Mutex<HashMap<PathBuf, FfmpegFeatures>> {
+    static CACHE: OnceLock<Mutex<HashMap<PathBuf, FfmpegFeatures>>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn detect_features_inner(path: &Path) -> Result<FfmpegFeatures, String> {
+    let (version, configuration) = parse_version_output(&run(path, "-version")?);
+
+    Ok(FfmpegFeatures {
+        version,
+        configuration,
+        encoders: parse_listing(&run(path, "-encoders")?),
+        muxers: parse_listing(&run(path, "-muxers")?),
+    })
+}
+
+fn run(path: &Path, flag: &str) -> Result<String, String> {
+    let output = Command::new(path)
+        .args(["-hide_banner", flag])
+        .output()
+        .map_err(|err| format!("执行 ffmpeg {flag} 失败: {err}"))?;
+
+    if !output.status.success() {
+        return Err(format!("ffmpeg {flag} 返回失败状态"));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Reads the version from `ffmpeg version 7.1 Copyright ...` and the flags
+/// from the `configuration:` line.
+fn parse_version_output(output: &str) -> (Option<String>, Vec<String>) {
+    let version = output.lines().find_map(|line| {
+        line.trim()
+            .strip_prefix("ffmpeg version ")
+            .and_then(|rest| rest.split_whitespace().next())
+            .map(str::to_string)
+    });
+    let configuration = output
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("configuration:"))
+        .map(|flags| flags.split_whitespace().map(str::to_string).collect())
+        .unwrap_or_default();
+
+    (version, configuration)
+}
+
+/// Names from the `-encoders`/`-muxers` tables: a legend, a dashed separator
+/// line, then rows of `<flags> <name> <description>`.
+fn parse_listing(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .skip_while(|line| {
+            let line = line.trim();
+            line.is_empty() || !line.chars().all(|ch| ch == '-')
+        })
+        .skip(1)
+        .filter_map(|line| line.split_whitespace().nth(1))
+        .flat_map(|names| names.split(','))
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        detect_features, parse_listing, parse_version_output, read_version, FfmpegFeatures,
+        FfmpegRequirement,
+    };
+
+    const VERSION_OUTPUT: &str = "ffmpeg version 6.1.1-3ubuntu5 Copyright (c) 2000-2023 the FFmpeg developers
+built with gcc 13 (Ubuntu 13.2.0-23ubuntu3)
+configuration: --prefix=/usr --extra-version=3ubuntu5 --toolchain=hardened --enable-gpl --enable-libmp3lame --enable-libopus --enable-libx264
+libavutil      58. 29.100 / 58. 29.100
+libavcodec     60. 31.102 / 60. 31.102
+";
+
+    const ENCODERS_OUTPUT: &str = "Encoders:
+ V..... = Video
+ A..... = Audio
+ S..... = Subtitle
+ .F.... = Frame-level multithreading
+ ..S... = Slice-level multithreading
+ ...X.. = Codec is experimental
+ ....B. = Supports draw_horiz_band
+ .....D = Supports direct rendering method 1
+ ------
+ V....D libx264              libx264 H.264 / AVC / MPEG-4 AVC / MPEG-4 part 10 (codec h264)
+ V....D libvpx-vp9           libvpx VP9 (codec vp9)
+ A....D aac                  AAC (Advanced Audio Coding)
+ A....D flac                 FLAC (Free Lossless Audio Codec)
+ A....D libmp3lame           libmp3lame MP3 (MPEG audio layer 3) (codec mp3)
+ A....D pcm_s16le            PCM signed 16-bit little-endian
+ S..... srt                  SubRip subtitle (codec subrip)
+";
+
+    const MUXERS_OUTPUT: &str = "Muxers:
+ D. = Demuxing supported
+ .E = Muxing supported
+ --
+  E adts            ADTS AAC (Advanced Audio Coding)
+  E flac            raw FLAC
+  E ipod            iPod H.264 MP4 (MPEG-4 Part 14)
+  E matroska        Matroska
+  E mov             QuickTime / MOV
+  E mp3             MP3 (MPEG audio layer 3)
+  E mp4             MP4 (MPEG-4 Part 14)
+  E wav             WAV / WAVE (Waveform Audio)
+  E webm            WebM
+";
+
+    fn features() -> FfmpegFeatures {
+        let (version, configuration) = parse_version_output(VERSION_OUTPUT);
+        FfmpegFeatures {
+            version,
+            configuration,
+            encoders: parse_listing(ENCODERS_OUTPUT),
+            muxers: parse_listing(MUXERS_OUTPUT),
+        }
+    }
+
+    #[test]
+    fn parses_version_and_configuration() {
+        let (version, configuration) = parse_version_output(VERSION_OUTPUT);
+        assert_eq!(version.as_deref(), Some("6.1.1-3ubuntu5"));
+        assert!(configuration.contains(&"--enable-libmp3lame".to_string()));
+        assert_eq!(configuration.len(), 7);
+
+        let (version, _) = parse_version_output(
+            "ffmpeg version N-118000-g0a1b2c3d4e-20250101 Copyright (c) 2000-2025 the FFmpeg developers\n",
+        );
+        assert_eq!(version.as_deref(), Some("N-118000-g0a1b2c3d4e-20250101"));
+        assert_eq!(parse_version_output("not ffmpeg"), (None, Vec::new()));
+    }
+
+    #[test]
+    fn parses_encoder_and_muxer_tables() {
+        let features = features();
+        assert_eq!(
+            features.encoders,
+            [
+                "libx264",
+                "libvpx-vp9",
+                "aac",
+                "flac",
+                "libmp3lame",
+                "pcm_s16le",
+                "srt"
+            ]
+        );
+        assert!(features.has_muxer("ipod"));
+        assert!(features.has_muxer("webm"));
+        assert!(!features.has_muxer("opus"));
+        assert_eq!(features.muxers.len(), 9);
+    }
+
+    #[test]
+    fn reports_unmet_requirements() {
+        let features = features();
+        let requirements = [
+            FfmpegRequirement::Encoder {
+                any_of: &["libmp3lame"],
+                output: "MP3 音频",
+            },
+            FfmpegRequirement::Encoder {
+                any_of: &["libopus"],
+                output: "Opus 音频",
+            },
+            FfmpegRequirement::Muxer {
+                name: "opus",
+                output: "Opus 音频",
+            },
+        ];
+
+        assert_eq!(
+            features.unmet(&requirements),
+            [
+                "当前 ffmpeg 缺少 libopus 编码器，可能无法生成 Opus 音频",
+                "当前 ffmpeg 不支持 opus 封装格式，可能无法生成 Opus 音频",
+            ]
+        );
+        assert!(FfmpegFeatures::default().unmet(&requirements).is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reads_the_version_of_a_binary() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir =
+            std::env::temp_dir().join(format!("yt-dlp-x-ffmpeg-version-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("create dir");
+        let write_script = |name: &str, output: &str| {
+            let path = dir.join(name);
+            std::fs::write(&path, format!("#!/bin/sh\necho '{output}'\n")).expect("write script");
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))
+                .expect("set permissions");
+            path
+        };
+
+        let ffmpeg = write_script("ffmpeg", "ffmpeg version 7.1.1 Copyright (c) 2000-2025");
+        assert_eq!(read_version(&ffmpeg).as_deref(), Ok("7.1.1"));
+
+        let other = write_script("other", "yt-dlp 2025.01.02");
+        assert!(read_version(&other).is_err());
+        assert!(read_version(&dir.join("missing")).is_err());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn retries_detection_after_a_failed_run() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir =
+            std::env::temp_dir().join(format!("yt-dlp-x-ffmpeg-detect-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).expect("create dir");
+        let ffmpeg = dir.join("ffmpeg");
+
+        assert_eq!(detect_features(&ffmpeg).await, FfmpegFeatures::default());
+
+        std::fs::write(
+            &ffmpeg,
+            "#!/bin/sh\necho 'ffmpeg version 7.1.1 Copyright (c) 2000-2025'\n",
+        )
+        .expect("write script");
+        std::fs::set_permissions(&ffmpeg, std::fs::Permissions::from_mode(0o755))
+            .expect("set permissions");
+        assert_eq!(
+            detect_features(&ffmpeg).await.version.as_deref(),
+            Some("7.1.1")
+        );
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,383 @@
+use sha2::{Digest, Sha256};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::{
+    fs::{self, OpenOptions},
+    io::{AsyncReadExt, AsyncWriteExt},
+    sync::watch,
+};
+
+use super::checksum::hex_sha256;
+
+/// Minimum gap between two progress reports.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+const CANCELLED: &str = "下载已取消";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransferProgress {
+    pub downloaded: u64,
+    pub total: Option<u64>,
+    pub bytes_per_second: u64,
+}
+
+pub type ProgressCallback = Arc<dyn Fn(TransferProgress) + Send + Sync>;
+
+/// Shared between an install and whoever may cancel it or watch its progress.
+#[derive(Clone)]
+pub struct DownloadControl {
+    cancelled: Arc<watch::Sender<bool>>,
+    on_progress: Option<ProgressCallback>,
+}
+
+impl Default for DownloadControl {
+    fn default() -> Self {
+        Self {
+            cancelled: Arc::new(watch::Sender::new(false)),
+            on_progress: None,
+        }
+    }
+}
+
+impl DownloadControl {
+    pub fn with_progress(on_progress: ProgressCallback) -> Self {
+        Self {
+            on_progress: Some(on_progress),
+            ..Self::default()
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.send_replace(true);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        *self.cancelled.borrow()
+    }
+
+    /// Resolves once `cancel` has been called, even before this was awaited.
+    async fn wait_cancelled(&self) {
+        let mut receiver = self.cancelled.subscribe();
+        // The sender lives in `self`, so this only returns once cancelled.
+        let _ = receiver.wait_for(|cancelled| *cancelled).await;
+    }
+
+    fn report(&self, progress: TransferProgress) {
+        if let Some(on_progress) = &self.on_progress {
+            on_progress(progress);
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DownloadedFile {
+    pub len: u64,
+    pub sha256: String,
+}
+
+/// Streams `url` into `part_path`, resuming a previous partial download when
+/// the server still serves the same file, and hashes the content on the way.
+/// The part file is kept on failure or cancellation so the next attempt can
+/// resume; callers remove it once they are done with the content.
+pub async fn download_file(
+    client: &reqwest::Client,
+    url: &str,
+    part_path: &Path,
+    control: &DownloadControl,
+) -> Result<DownloadedFile, String> {
+    if let Some(parent) = part_path.parent() {
+        fs::create_dir_all(parent)
+            .await
+            .map_err(|err| format!("创建目录失败: {err}"))?;
+    }
+
+    let validator_path = validator_path(part_path);
+    let existing_len = fs::metadata(part_path)
+        .await
+        .map(|metadata| metadata.len())
+        .unwrap_or(0);
+    let validator = fs::read_to_string(&validator_path).await.ok();
+
+    if control.is_cancelled() {
+        return Err(CANCELLED.into());
+    }
+
+    let mut request = client.get(url);
+    if let (true, Some(validator)) = (existing_len > 0, validator.as_deref()) {
+        request = request
+            .header("Range", format!("bytes={existing_len}-"))
+            .header("If-Range", validator.trim());
+    }
+
+    let mut response = tokio::select! {
+        response = request.send() => response.map_err(|err| format!("下载失败: {err}"))?,
+        _ = control.wait_cancelled() => return Err(CANCELLED.into()),
+    };
+
+    let status = response.status();
+    let resumed = status == reqwest::StatusCode::PARTIAL_CONTENT;
+    if status == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+        // The part file does not belong to what the server has now; start over.
+        remove_partial(part_path).await;
+        return Box::pin(download_file(client, url, part_path, control)).await;
+    }
+    if !status.is_success() {
+        return Err(format!("下载失败，状态码: {status}"));
+    }
+
+    let mut hasher = Sha256::new();
+    let mut file = if resumed {
+        hash_existing(part_path, &mut hasher).await?;
+        OpenOptions::new()
+            .append(true)
+            .open(part_path)
+            .await
+            .map_err(|err| format!("打开临时文件失败: {err}"))?
+    } else {
+        let validator = response
+            .headers()
+            .get("ETag")
+            .or_else(|| response.headers().get("Last-Modified"))
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        match validator {
+            Some(validator) => fs::write(&validator_path, validator).await,
+            None => fs::remove_file(&validator_path).await.or(Ok(())),
+        }
+        .map_err(|err| format!("写入临时文件失败: {err}"))?;
+
+        fs::File::create(part_path)
+            .await
+            .map_err(|err| format!("创建临时文件失败: {err}"))?
+    };
+
+    let mut downloaded = if resumed { existing_len } else { 0 };
+    let total = response.content_length().map(|len| len + downloaded);
+    let started = Instant::now();
+    let session_start = downloaded;
+    let mut last_report: Option<Instant> = None;
+
+    loop {
+        let chunk = tokio::select! {
+            chunk = response.chunk() => chunk.map_err(|err| format!("读取下载内容失败: {err}"))?,
+            _ = control.wait_cancelled() => {
+                let _ = file.flush().await;
+                return Err(CANCELLED.into());
+            }
+        };
+        let Some(chunk) = chunk else {
+            break;
+        };
+
+        file.write_all(&chunk)
+            .await
+            .map_err(|err| format!("写入临时文件失败: {err}"))?;
+        hasher.update(&chunk);
+        downloaded += chunk.len() as u64;
+
+        if last_report.is_none_or(|at| at.elapsed() >= PROGRESS_INTERVAL) {
+            last_report = Some(Instant::now());
+            control.report(progress(downloaded, total, session_start, started));
+        }
+    }
+
+    file.flush()
+        .await
+        .map_err(|err| format!("写入临时文件失败: {err}"))?;
+    control.report(progress(downloaded, total, session_start, started));
+
+    if let Some(total) = total {
+        if downloaded != total {
+            return Err(format!("下载内容不完整 ({downloaded}/{total} 字节)"));
+        }
+    }
+
+    Ok(DownloadedFile {
+        len: downloaded,
+        sha256: hex_sha256(hasher),
+    })
+}
+
+/// Deletes a part file together with the validator used to resume it.
+pub async fn remove_partial(part_path: &Path) {
+    let _ = fs::remove_file(part_path).await;
+    let _ = fs::remove_file(validator_path(part_path)).await;
+}
+
+fn progress(
+    downloaded: u64,
+    total: Option<u64>,
+    session_start: u64,
+    started: Instant,
+) -> TransferProgress {
+    let elapsed = started.elapsed().as_secs_f64();
+    let bytes_per_second = if elapsed > 0.0 {
+        ((downloaded - session_start) as f64 / elapsed) as u64
+    } else {
+        0
+    };
+
+    TransferProgress {
+        downloaded,
+        total,
+        bytes_per_second,
+    }
+}
+
+async fn hash_existing(path: &Path, hasher: &mut Sha256) -> Result<(), String> {
+    let mut file = fs::File::open(path)
+        .await
+        .map_err(|err| format!("读取临时文件失败: {err}"))?;
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let read = file
+            .read(&mut buffer)
+            .await
+            .map_err(|err| format!("读取临时文件失败: {err}"))?;
+        if read == 0 {
+            return Ok(());
+        }
+        hasher.update(&buffer[..read]);
+    }
+}
+
+/// The ETag or Last-Modified value of the response a part file came from.
+fn validator_path(part_path: &Path) -> PathBuf {
+    let mut name = part_path
+        .file_name()
+        .map(|name| name.to_os_string())
+        .unwrap_or_default();
+    name.push(".validator");
+    part_path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{download_file, validator_path, DownloadControl, TransferProgress};
+    use crate::{
+        test_support::{Route, TestServer},
+        utils::checksum::hex_sha256,
+    };
+    use sha2::{Digest, Sha256};
+    use std::{
+        path::{Path, PathBuf},
+        sync::{Arc, Mutex},
+    };
+
+    fn payload() -> Vec<u8> {
+        (0..200_000u32).map(|value| (value % 251) as u8).collect()
+    }
+
+    fn sha256(bytes: &[u8]) -> String {
+        hex_sha256(Sha256::new_with_prefix(bytes))
+    }
+
+    fn part_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("yt-dlp-x-http-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir.join("file.part")
+    }
+
+    fn remove_part_dir(part: &Path) {
+        if let Some(dir) = part.parent() {
+            let _ = std::fs::remove_dir_all(dir);
+        }
+    }
+
+    #[tokio::test]
+    async fn streams_to_disk_and_reports_progress() {
+        let body = payload();
+        let server = TestServer::start(vec![Route::ok("/file", body.clone())]);
+        let part = part_path("fresh");
+        let reports: Arc<Mutex<Vec<TransferProgress>>> = Arc::default();
+        let control = DownloadControl::with_progress({
+            let reports = Arc::clone(&reports);
+            Arc::new(move |progress| reports.lock().expect("lock").push(progress))
+        });
+
+        let file = download_file(
+            &reqwest::Client::new(),
+            &format!("{}/file", server.base_url),
+            &part,
+            &control,
+        )
+        .await
+        .expect("download should succeed");
+
+        assert_eq!(file.len, body.len() as u64);
+        assert_eq!(file.sha256, sha256(&body));
+        assert_eq!(std::fs::read(&part).expect("read part"), body);
+        let last = *reports.lock().expect("lock").last().expect("a report");
+        assert_eq!(last.downloaded, body.len() as u64);
+        assert_eq!(last.total, Some(body.len() as u64));
+        remove_part_dir(&part);
+    }
+
+    #[tokio::test]
+    async fn resumes_a_partial_download_of_the_same_file() {
+        let body = payload();
+        let server = TestServer::start(vec![Route::ok("/file", body.clone())]);
+        let part = part_path("resume");
+        std::fs::create_dir_all(part.parent().expect("parent")).expect("create dir");
+        std::fs::write(&part, &body[..70_000]).expect("write part");
+        std::fs::write(validator_path(&part), Route::ETAG).expect("write validator");
+
+        let file = download_file(
+            &reqwest::Client::new(),
+            &format!("{}/file", server.base_url),
+            &part,
+            &DownloadControl::default(),
+        )
+        .await
+        .expect("download should resume");
+
+        assert_eq!(file.sha256, sha256(&body));
+        assert_eq!(std::fs::read(&part).expect("read part"), body);
+        assert_eq!(server.requests_with_range(), 1);
+        remove_part_dir(&part);
+    }
+
+    #[tokio::test]
+    async fn restarts_when_the_remote_file_changed() {
+        let body = payload();
+        let server = TestServer::start(vec![Route::ok("/file", body.clone())]);
+        let part = part_path("changed");
+        std::fs::create_dir_all(part.parent().expect("parent")).expect("create dir");
+        std::fs::write(&part, b"stale bytes from an older release").expect("write part");
+        std::fs::write(validator_path(&part), "\"old\"").expect("write validator");
+
+        let file = download_file(
+            &reqwest::Client::new(),
+            &format!("{}/file", server.base_url),
+            &part,
+            &DownloadControl::default(),
+        )
+        .await
+        .expect("download should restart");
+
+        assert_eq!(file.sha256, sha256(&body));
+        assert_eq!(std::fs::read(&part).expect("read part"), body);
+        remove_part_dir(&part);
+    }
+
+    #[tokio::test]
+    async fn stops_when_cancelled() {
+        let server = TestServer::start(vec![Route::ok("/file", payload())]);
+        let part = part_path("cancel");
+        let control = DownloadControl::default();
+        control.cancel();
+
+        let result = download_file(
+            &reqwest::Client::new(),
+            &format!("{}/file", server.base_url),
+            &part,
+            &control,
+        )
+        .await;
+
+        assert_eq!(result, Err("下载已取消".to_string()));
+        remove_part_dir(&part);
+    }
+}
@@ -1,3 +1,10 @@
+pub mod checksum;
 pub mod ffmpeg;
+pub mod ffmpeg_features;
+pub mod http_download;
 pub mod path_search;
+pub mod process;
+pub mod storage;
 pub mod yt_dlp;
+pub mod yt_dlp_release;
+pub mod yt_dlp_store;
@@ -0,0 +1,109 @@
+use std::time::Duration;
+use tokio::process::{Child, Command};
+
+const GRACEFUL_EXIT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Put the child into its own process group so the whole tree (yt-dlp plus the
+/// ffmpeg processes it spawns) can be signalled at once.
+pub fn isolate_process_group(command: &mut Command) {
+    #[cfg(unix)]
+    {
+        command.process_group(0);
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = command;
+    }
+}
+
+/// Ask the process tree to exit, falling back to a forced kill when it does not
+/// stop within the grace period.
+pub async fn terminate_process_tree(child: &mut Child) -> Result<(), String> {
+    let Some(pid) = child.id() else {
+        return Ok(());
+    };
+
+    if request_graceful_exit(pid).is_ok() {
+        if let Ok(result) = tokio::time::timeout(GRACEFUL_EXIT_TIMEOUT, child.wait()).await {
+            return result
+                .map(|_| ())
+                .map_err(|err| format!("等待进程退出失败: {err}"));
+        }
+    }
+
+    if let Err(err) = force_kill_tree(pid) {
+        eprintln!("强制结束进程树失败: {err}");
+    }
+
+    child
+        .kill()
+        .await
+        .map_err(|err| format!("结束进程失败: {err}"))
+}
+
+#[cfg(unix)]
+fn request_graceful_exit(pid: u32) -> Result<(), String> {
+    // yt-dlp treats SIGINT like Ctrl+C and leaves resumable partial files behind.
+    signal_process_group(pid, "-INT")
+}
+
+#[cfg(unix)]
+fn force_kill_tree(pid: u32) -> Result<(), String> {
+    signal_process_group(pid, "-KILL")
+}
+
+#[cfg(unix)]
+fn signal_process_group(pid: u32, signal: &str) -> Result<(), String> {
+    let status = std::process::Command::new("kill")
+        .arg(signal)
+        .arg("--")
+        .arg(format!("-{pid}"))
+        .status()
+        .map_err(|err| format!("执行 kill 命令失败: {err}"))?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("kill 命令返回失败状态: {status}"))
+    }
+}
+
+#[cfg(windows)]
+fn request_graceful_exit(pid: u32) -> Result<(), String> {
+    run_taskkill(pid, false)
+}
+
+#[cfg(windows)]
+fn force_kill_tree(pid: u32) -> Result<(), String> {
+    run_taskkill(pid, true)
+}
+
+#[cfg(windows)]
+fn run_taskkill(pid: u32, force: bool) -> Result<(), String> {
+    let mut command = std::process::Command::new("taskkill");
+    command.arg("/PID").arg(pid.to_string()).arg("/T");
+    if force {
+        command.arg("/F");
+    }
+
+    let status = command
+        .status()
+        .map_err(|err| format!("执行 taskkill 命令失败: {err}"))?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("taskkill 命令返回失败状态: {status}"))
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn request_graceful_exit(_pid: u32) -> Result<(), String> {
+    Err("当前平台暂不支持结束进程树".into())
+}
+
+#[cfg(not(any(unix, windows)))]
+fn force_kill_tree(_pid: u32) -> Result<(), String> {
+    Err("当前平台暂不支持结束进程树".into())
+}
@@ -0,0 +1,48 @@
+use serde::{de::DeserializeOwned, Serialize};
+use std::path::{Path, PathBuf};
+
+pub fn data_file_path(file_name: &str) -> Result<PathBuf, String> {
+    let dirs = super::yt_dlp::project_dirs()?;
+    Ok(dirs.data_dir().join(file_name))
+}
+
+pub fn load_json<T: DeserializeOwned>(path: &Path) -> Result<Option<T>, String> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(format!("读取 {} 失败: {err}", path.display())),
+    };
+
+    serde_json::from_str(&content)
+        .map(Some)
+        .map_err(|err| format!("解析 {} 失败: {err}", path.display()))
+}
+
+pub fn save_json<T: Serialize>(path: &Path, value: &T) -> Result<(), String> {
+    let content =
+        serde_json::to_vec_pretty(value).map_err(|err| format!("序列化数据失败: {err}"))?;
+    write_atomic(path, &content)
+}
+
+/// Writes through a sibling temp file and renames it, so a crash mid-write never
+/// leaves a truncated state file behind.
+pub fn write_atomic(path: &Path, content: &[u8]) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|err| format!("创建目录失败: {err}"))?;
+    }
+
+    let mut temp_name = path
+        .file_name()
+        .map(|name| name.to_os_string())
+        .unwrap_or_default();
+    temp_name.push(".tmp");
+    let temp_path = path.with_file_name(temp_name);
+
+    std::fs::write(&temp_path, content)
+        .map_err(|err| format!("写入 {} 失败: {err}", temp_path.display()))?;
+
+    std::fs::rename(&temp_path, path).map_err(|err| {
+        let _ = std::fs::remove_file(&temp_path);
+        format!("保存 {} 失败: {err}", path.display())
+    })
+}
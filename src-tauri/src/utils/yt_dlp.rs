@@ -1,18 +1,27 @@
 use directories_next::{BaseDirs, ProjectDirs, UserDirs};
-use sha2::{Digest, Sha256};
 use std::{
     collections::HashMap,
     path::{Path, PathBuf},
     process::Command,
     sync::{Mutex, OnceLock},
+    time::SystemTime,
 };
 use tokio::fs;
 use which::which;
 
+use super::{
+    checksum::{self, ChecksumSource},
+    http_download::{self, DownloadControl},
+    yt_dlp_release::{self, ReleaseEndpoints, YtDlpRelease},
+    yt_dlp_store::{BinaryStore, InstalledYtDlp},
+};
+
 #[derive(Debug, Clone, Copy)]
 pub enum BinarySource {
     System,
     Bundled,
+    /// The path configured in the settings.
+    Custom,
 }
 
 #[derive(Debug, Clone, Default, PartialEq, Eq)]
@@ -20,9 +29,18 @@ pub struct RuntimeCapabilities {
     pub supports_progress_template: bool,
     pub supports_paths_temp: bool,
     pub supports_add_headers: bool,
+    pub supports_embed_metadata: bool,
+    pub supports_embed_chapters: bool,
+    pub supports_embed_info_json: bool,
+    pub supports_sponsorblock: bool,
+    pub supports_no_quiet: bool,
 }
 
 pub fn detect_existing() -> Result<Option<(PathBuf, BinarySource)>, String> {
+    if let Some(path) = custom_binary()? {
+        return Ok(Some((path, BinarySource::Custom)));
+    }
+
     if let Some(path) = detect_bundled_binary()? {
         if validate_binary(&path).is_ok() {
             return Ok(Some((path, BinarySource::Bundled)));
@@ -39,29 +57,68 @@ pub fn detect_existing() -> Result<Option<(PathBuf, BinarySource)>, String> {
     Ok(None)
 }
 
-pub async fn ensure_available() -> Result<(PathBuf, BinarySource), String> {
+/// Like `detect_existing`, but first repairs the executable bit on the
+/// bundled binary. `None` means yt-dlp has to be installed.
+pub async fn find_available() -> Result<Option<(PathBuf, BinarySource)>, String> {
+    if let Some(path) = custom_binary()? {
+        return Ok(Some((path, BinarySource::Custom)));
+    }
+
     if let Some(path) = detect_bundled_binary()? {
         ensure_executable_permissions(&path).await?;
         if validate_binary(&path).is_ok() {
-            return Ok((path, BinarySource::Bundled));
+            return Ok(Some((path, BinarySource::Bundled)));
         }
         let _ = fs::remove_file(&path).await;
     }
 
     if let Some(path) = detect_system_binary() {
         if validate_binary(&path).is_ok() {
-            return Ok((path, BinarySource::System));
+            return Ok(Some((path, BinarySource::System)));
         }
     }
 
-    let path = bundled_binary_path()?;
-    download_to(&path).await?;
-    Ok((path, BinarySource::Bundled))
+    Ok(None)
+}
+
+pub async fn install(release: &YtDlpRelease, control: &DownloadControl) -> Result<PathBuf, String> {
+    // Parallel downloads can all hit a broken extractor and ask for an update at once.
+    static INSTALL_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+    let _guard = INSTALL_LOCK.lock().await;
+
+    let staged_path = bin_dir()?.join("incoming").join(binary_file_name());
+    let sha256 = download_to(&staged_path, release, &ReleaseEndpoints::default(), control).await?;
+    let version = get_version(&staged_path)
+        .and_then(|version| yt_dlp_release::normalize_tag(Some(&version)))
+        .and_then(|version| version.ok_or_else(|| "无法解析 yt-dlp 版本号".to_string()));
+    let version = match version {
+        Ok(version) => version,
+        Err(err) => {
+            let _ = fs::remove_file(&staged_path).await;
+            return Err(err);
+        }
+    };
+
+    let store = binary_store()?;
+    let installed_at = crate::now_millis();
+    tokio::task::spawn_blocking(move || store.add(&version, &sha256, &staged_path, installed_at))
+        .await
+        .map_err(|err| format!("保存 yt-dlp 失败: {err}"))?
 }
 
-pub async fn install_latest() -> Result<PathBuf, String> {
-    let path = bundled_binary_path()?;
-    download_to(&path).await?;
+/// Bundled versions kept for rollback, newest install first.
+pub fn list_installed() -> Result<Vec<InstalledYtDlp>, String> {
+    binary_store()?.list()
+}
+
+/// Switches downloads to an already installed bundled version.
+pub async fn activate(version: &str) -> Result<PathBuf, String> {
+    let store = binary_store()?;
+    let version = version.trim().to_string();
+    let path = tokio::task::spawn_blocking(move || store.activate(&version))
+        .await
+        .map_err(|err| format!("切换 yt-dlp 版本失败: {err}"))??;
+    validate_binary(&path)?;
     Ok(path)
 }
 
@@ -80,7 +137,8 @@ pub fn get_version(path: &Path) -> Result<String, String> {
         });
     }
 
-    let version = String::from_utf8_lossy(&output.stdout)
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let version = stdout
         .lines()
         .next()
         .map(str::trim)
@@ -148,6 +206,11 @@ fn detect_capabilities_inner(path: &Path) -> Result<RuntimeCapabilities, String>
             || content.contains("temp:")
             || (content.contains("--paths") && content.contains(" temp")),
         supports_add_headers: content.contains("--add-headers"),
+        supports_embed_metadata: content.contains("--embed-metadata"),
+        supports_embed_chapters: content.contains("--embed-chapters"),
+        supports_embed_info_json: content.contains("--embed-info-json"),
+        supports_sponsorblock: content.contains("--sponsorblock-mark"),
+        supports_no_quiet: content.contains("--no-quiet"),
     })
 }
 
@@ -166,8 +229,51 @@ fn detect_system_binary() -> Option<PathBuf> {
     super::path_search::locate_macos_binary(&["yt-dlp", "yt-dlp_macos"])
 }
 
+/// A configured path is used as is: falling back to another yt-dlp would hide
+/// that the one the user chose is broken.
+fn custom_binary() -> Result<Option<PathBuf>, String> {
+    let Some(path) = crate::settings::current().yt_dlp_path.map(PathBuf::from) else {
+        return Ok(None);
+    };
+
+    validate_custom_binary(&path).map_err(|err| format!("自定义 yt-dlp 不可用: {err}"))?;
+    Ok(Some(path))
+}
+
+/// Runs `--version` once per path and modification time, so every probe and
+/// download does not spawn yt-dlp again; replacing the binary re-validates it.
+fn validate_custom_binary(path: &Path) -> Result<(), String> {
+    let modified = std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .map_err(|err| format!("无法读取 {}: {err}", path.display()))?;
+    let validated = custom_binary_cache()
+        .lock()
+        .ok()
+        .and_then(|cache| cache.get(path).copied());
+    if validated == Some(modified) {
+        return Ok(());
+    }
+
+    validate_binary(path)?;
+    if let Ok(mut cache) = custom_binary_cache().lock() {
+        cache.insert(path.to_path_buf(), modified);
+    }
+    Ok(())
+}
+
+fn custom_binary_cache() -> &'static Mutex<HashMap<PathBuf, SystemTime>> {
+    static CACHE: OnceLock<Mutex<HashMap<PathBuf, SystemTime>>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// The active versioned binary, or the single `bin/<asset>` earlier releases
+/// of the app installed.
 fn detect_bundled_binary() -> Result<Option<PathBuf>, String> {
-    let path = bundled_binary_path()?;
+    if let Some(path) = binary_store()?.active_path()? {
+        return Ok(Some(path));
+    }
+
+    let path = bin_dir()?.join(binary_file_name());
     if path.exists() {
         Ok(Some(path))
     } else {
@@ -175,12 +281,16 @@ fn detect_bundled_binary() -> Result<Option<PathBuf>, String> {
     }
 }
 
-fn bundled_binary_path() -> Result<PathBuf, String> {
+fn bin_dir() -> Result<PathBuf, String> {
     let dirs = project_dirs()?;
-    Ok(dirs.data_dir().join("bin").join(binary_file_name()))
+    Ok(dirs.data_dir().join("bin"))
+}
+
+fn binary_store() -> Result<BinaryStore, String> {
+    Ok(BinaryStore::new(bin_dir()?, binary_file_name()))
 }
 
-fn project_dirs() -> Result<ProjectDirs, String> {
+pub fn project_dirs() -> Result<ProjectDirs, String> {
     ProjectDirs::from("com", "yt-dlp-x", "yt-dlp-x")
         .ok_or_else(|| "无法定位应用数据目录".to_string())
 }
@@ -199,18 +309,12 @@ fn release_asset_name() -> &'static str {
     }
 }
 
-fn download_url() -> String {
-    format!(
-        "https://github.com/yt-dlp/yt-dlp/releases/latest/download/{}",
-        release_asset_name()
-    )
-}
-
-fn checksums_url() -> &'static str {
-    "https://github.com/yt-dlp/yt-dlp/releases/latest/download/SHA2-256SUMS"
-}
-
-async fn download_to(target_path: &Path) -> Result<(), String> {
+async fn download_to(
+    target_path: &Path,
+    release: &YtDlpRelease,
+    endpoints: &ReleaseEndpoints,
+    control: &DownloadControl,
+) -> Result<String, String> {
     let parent = target_path
         .parent()
         .ok_or_else(|| "无法确定 yt-dlp 存储目录".to_string())?;
@@ -219,86 +323,39 @@ async fn download_to(target_path: &Path) -> Result<(), String> {
         .map_err(|err| format!("创建目录失败: {err}"))?;
 
     let client = reqwest::Client::new();
-    let binary_bytes = download_binary_bytes(&client).await?;
-
-    if binary_bytes.len() < 1024 {
+    let checksums = ChecksumSource::Listing {
+        url: release.checksums_url(endpoints),
+        asset_name: release_asset_name().into(),
+    };
+    let expected = checksum::fetch_expected(&client, &checksums, "yt-dlp").await?;
+    let binary_url = release.binary_url(endpoints, release_asset_name());
+    let part_path = temporary_binary_path(target_path);
+    let downloaded =
+        http_download::download_file(&client, &binary_url, &part_path, control).await?;
+
+    if downloaded.len < 1024 {
+        http_download::remove_partial(&part_path).await;
         return Err("下载的 yt-dlp 文件异常（文件体积过小）".into());
     }
 
-    verify_download_checksum(&client, &binary_bytes).await?;
-    write_validated_binary(target_path, &binary_bytes).await
-}
-
-async fn download_binary_bytes(client: &reqwest::Client) -> Result<Vec<u8>, String> {
-    let response = client
-        .get(download_url())
-        .send()
-        .await
-        .map_err(|err| format!("下载 yt-dlp 失败: {err}"))?;
-
-    if !response.status().is_success() {
-        return Err(format!("下载 yt-dlp 失败，状态码: {}", response.status()));
-    }
-
-    response
-        .bytes()
-        .await
-        .map(|bytes| bytes.to_vec())
-        .map_err(|err| format!("读取下载内容失败: {err}"))
-}
-
-async fn verify_download_checksum(client: &reqwest::Client, bytes: &[u8]) -> Result<(), String> {
-    let checksums = client
-        .get(checksums_url())
-        .send()
-        .await
-        .map_err(|err| format!("下载 yt-dlp 校验清单失败: {err}"))?;
-
-    if !checksums.status().is_success() {
-        return Err(format!(
-            "下载 yt-dlp 校验清单失败，状态码: {}",
-            checksums.status()
-        ));
-    }
-
-    let body = checksums
-        .bytes()
-        .await
-        .map_err(|err| format!("读取 yt-dlp 校验清单失败: {err}"))?;
-    let body = String::from_utf8_lossy(&body);
-
-    let expected = find_release_checksum(&body, release_asset_name()).ok_or_else(|| {
-        format!(
-            "未在 SHA2-256SUMS 中找到 {} 的校验值",
-            release_asset_name()
-        )
-    })?;
-
-    let actual = hex_sha256(bytes);
-    if actual != expected {
+    if downloaded.sha256 != expected {
+        http_download::remove_partial(&part_path).await;
         return Err("yt-dlp 下载校验失败，请稍后重试".into());
     }
 
-    Ok(())
+    let installed = install_validated_binary(&part_path, target_path).await;
+    http_download::remove_partial(&part_path).await;
+    installed.map(|_| downloaded.sha256)
 }
 
-async fn write_validated_binary(target_path: &Path, bytes: &[u8]) -> Result<(), String> {
-    let temp_path = temporary_binary_path(target_path);
-    if temp_path.exists() {
-        let _ = fs::remove_file(&temp_path).await;
-    }
-
-    if let Err(err) = fs::write(&temp_path, bytes).await {
-        return Err(format!("写入 yt-dlp 文件失败: {err}"));
-    }
-
-    if let Err(err) = ensure_executable_permissions(&temp_path).await {
-        let _ = fs::remove_file(&temp_path).await;
+async fn install_validated_binary(temp_path: &Path, target_path: &Path) -> Result<(), String> {
+    if let Err(err) = ensure_executable_permissions(temp_path).await {
+        let _ = fs::remove_file(temp_path).await;
         return Err(err);
     }
 
-    if let Err(err) = validate_binary(&temp_path) {
-        let _ = fs::remove_file(&temp_path).await;
+    if let Err(err) = validate_binary(temp_path) {
+        let _ = fs::remove_file(temp_path).await;
         return Err(format!("下载的 yt-dlp 无法运行: {err}"));
     }
 
@@ -306,8 +363,8 @@ async fn write_validated_binary(target_path: &Path, bytes: &[u8]) -> Result<(),
         let _ = fs::remove_file(target_path).await;
     }
 
-    if let Err(err) = fs::rename(&temp_path, target_path).await {
-        let _ = fs::remove_file(&temp_path).await;
+    if let Err(err) = fs::rename(temp_path, target_path).await {
+        let _ = fs::remove_file(temp_path).await;
         return Err(format!("替换 yt-dlp 文件失败: {err}"));
     }
 
@@ -338,7 +395,7 @@ fn temporary_binary_path(target_path: &Path) -> PathBuf {
 
     target_path
         .parent()
-        .map(|parent| parent.join(file_name))
+        .map(|parent| parent.join(&file_name))
         .unwrap_or_else(|| PathBuf::from(file_name))
 }
 
@@ -346,39 +403,6 @@ fn validate_binary(path: &Path) -> Result<(), String> {
     get_version(path).map(|_| ())
 }
 
-fn find_release_checksum(content: &str, asset_name: &str) -> Option<String> {
-    content.lines().find_map(|line| {
-        let trimmed = line.trim();
-        if trimmed.is_empty() {
-            return None;
-        }
-
-        let mut parts = trimmed.split_whitespace();
-        let checksum = parts.next()?;
-        let filename = parts.last()?;
-
-        let normalized_name = filename
-            .trim_start_matches('*')
-            .trim_start_matches("./")
-            .trim();
-
-        if normalized_name != asset_name || checksum.len() != 64 {
-            return None;
-        }
-
-        if checksum.chars().all(|char| char.is_ascii_hexdigit()) {
-            Some(checksum.to_ascii_lowercase())
-        } else {
-            None
-        }
-    })
-}
-
-fn hex_sha256(bytes: &[u8]) -> String {
-    let digest = Sha256::digest(bytes);
-    digest.iter().map(|value| format!("{value:02x}")).collect()
-}
-
 #[cfg(unix)]
 async fn ensure_executable_permissions(path: &Path) -> Result<(), String> {
     use std::os::unix::fs::PermissionsExt;
@@ -400,24 +424,114 @@ async fn ensure_executable_permissions(_path: &Path) -> Result<(), String> {
 
 #[cfg(test)]
 mod tests {
-    use super::find_release_checksum;
-
-    #[test]
-    fn parses_checksum_from_sums_file() {
-        let sums = "abc123  yt-dlp\nffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff *yt-dlp.exe\n";
-
-        let value = find_release_checksum(sums, "yt-dlp.exe").expect("checksum should be found");
-
+    use super::{download_to, release_asset_name, validate_custom_binary};
+    use crate::{
+        test_support::{Route, TestServer},
+        utils::{
+            checksum::hex_sha256,
+            http_download::DownloadControl,
+            yt_dlp_release::{ReleaseChannel, ReleaseEndpoints, YtDlpRelease},
+        },
+    };
+    use sha2::{Digest, Sha256};
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn installs_pinned_release_verified_against_its_channel_checksums() {
+        let script = format!("#!/bin/sh\necho 2025.01.02.000001\n{}", "#\n".repeat(600));
+        let checksum = hex_sha256(Sha256::new_with_prefix(&script));
+        let asset = release_asset_name();
+        let release_dir = "/yt-dlp/yt-dlp-nightly-builds/releases/download/2025.01.02.000001";
+        let server = TestServer::start(vec![
+            Route::ok(&format!("{release_dir}/{asset}"), script.clone()),
+            Route::ok(
+                &format!("{release_dir}/SHA2-256SUMS"),
+                format!("{checksum}  {asset}\n"),
+            ),
+            Route::ok(
+                &format!("/yt-dlp/yt-dlp-nightly-builds/releases/latest/download/{asset}"),
+                script.clone(),
+            ),
+            Route::ok(
+                "/yt-dlp/yt-dlp-nightly-builds/releases/latest/download/SHA2-256SUMS",
+                format!("{}  {asset}\n", "0".repeat(64)),
+            ),
+        ]);
+        let endpoints = ReleaseEndpoints {
+            download_base: server.base_url.clone(),
+            api_base: server.base_url.clone(),
+        };
+        let target = std::env::temp_dir()
+            .join(format!("yt-dlp-x-install-{}", std::process::id()))
+            .join(asset);
+
+        let pinned = YtDlpRelease {
+            channel: ReleaseChannel::Nightly,
+            tag: Some("2025.01.02.000001".into()),
+        };
+        let sha256 = download_to(&target, &pinned, &endpoints, &DownloadControl::default())
+            .await
+            .expect("pinned release should install");
+        assert_eq!(sha256, checksum);
         assert_eq!(
-            value,
-            "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff"
+            super::get_version(&target).as_deref(),
+            Ok("2025.01.02.000001")
+        );
+
+        let latest = YtDlpRelease {
+            channel: ReleaseChannel::Nightly,
+            tag: None,
+        };
+        assert!(
+            download_to(&target, &latest, &endpoints, &DownloadControl::default())
+                .await
+                .is_err()
         );
+
+        let _ = std::fs::remove_dir_all(target.parent().expect("parent"));
     }
 
+    #[cfg(unix)]
     #[test]
-    fn ignores_invalid_checksum_rows() {
-        let sums = "thisisnothex  yt-dlp\n";
-        let value = find_release_checksum(sums, "yt-dlp");
-        assert!(value.is_none());
+    fn validates_a_custom_binary_once_until_it_changes() {
+        use std::{
+            os::unix::fs::PermissionsExt,
+            time::{Duration, SystemTime},
+        };
+
+        let dir = std::env::temp_dir().join(format!("yt-dlp-x-custom-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).expect("create dir");
+        let runs = dir.join("runs");
+        let binary = dir.join("yt-dlp");
+        std::fs::write(
+            &binary,
+            format!(
+                "#!/bin/sh\necho run >> '{}'\necho 2025.01.02\n",
+                runs.display()
+            ),
+        )
+        .expect("write script");
+        std::fs::set_permissions(&binary, std::fs::Permissions::from_mode(0o755))
+            .expect("make executable");
+        let run_count = || {
+            std::fs::read_to_string(&runs)
+                .map(|content| content.lines().count())
+                .unwrap_or(0)
+        };
+
+        validate_custom_binary(&binary).expect("valid binary");
+        validate_custom_binary(&binary).expect("cached");
+        assert_eq!(run_count(), 1);
+
+        std::fs::File::options()
+            .write(true)
+            .open(&binary)
+            .and_then(|file| file.set_modified(SystemTime::now() + Duration::from_secs(60)))
+            .expect("touch binary");
+        validate_custom_binary(&binary).expect("revalidated");
+        assert_eq!(run_count(), 2);
+
+        let _ = std::fs::remove_dir_all(&dir);
     }
 }
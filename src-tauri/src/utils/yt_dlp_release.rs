@@ -0,0 +1,347 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::cmp::Ordering;
+
+const GITHUB_BASE: &str = "https://github.com";
+const GITHUB_API_BASE: &str = "https://api.github.com";
+const VERSION_LIST_SIZE: usize = 30;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReleaseChannel {
+    #[default]
+    Stable,
+    Nightly,
+    Master,
+}
+
+impl ReleaseChannel {
+    fn repository(self) -> &'static str {
+        match self {
+            ReleaseChannel::Stable => "yt-dlp/yt-dlp",
+            ReleaseChannel::Nightly => "yt-dlp/yt-dlp-nightly-builds",
+            ReleaseChannel::Master => "yt-dlp/yt-dlp-master-builds",
+        }
+    }
+}
+
+/// The newest build of a channel, or the pinned `tag` from that channel's
+/// repository.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct YtDlpRelease {
+    pub channel: ReleaseChannel,
+    pub tag: Option<String>,
+}
+
+impl YtDlpRelease {
+    pub fn binary_url(&self, endpoints: &ReleaseEndpoints, asset_name: &str) -> String {
+        self.asset_url(endpoints, asset_name)
+    }
+
+    /// Every channel publishes its own `SHA2-256SUMS` next to the binaries.
+    pub fn checksums_url(&self, endpoints: &ReleaseEndpoints) -> String {
+        self.asset_url(endpoints, "SHA2-256SUMS")
+    }
+
+    fn asset_url(&self, endpoints: &ReleaseEndpoints, asset_name: &str) -> String {
+        let base = format!(
+            "{}/{}/releases",
+            endpoints.download_base.trim_end_matches('/'),
+            self.channel.repository()
+        );
+        match &self.tag {
+            Some(tag) => format!("{base}/download/{tag}/{asset_name}"),
+            None => format!("{base}/latest/download/{asset_name}"),
+        }
+    }
+}
+
+/// Hosts release downloads and the release listing API are fetched from;
+/// GitHub unless a test points them at a local stand-in.
+#[derive(Debug, Clone)]
+pub struct ReleaseEndpoints {
+    pub download_base: String,
+    pub api_base: String,
+}
+
+impl Default for ReleaseEndpoints {
+    fn default() -> Self {
+        Self {
+            download_base: GITHUB_BASE.into(),
+            api_base: GITHUB_API_BASE.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct YtDlpVersion {
+    #[serde(alias = "tag_name")]
+    pub tag: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default, alias = "published_at")]
+    pub published_at: Option<String>,
+    #[serde(default)]
+    pub prerelease: bool,
+    #[serde(default, alias = "body")]
+    pub release_notes: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateCheck {
+    pub current: Option<String>,
+    pub latest: String,
+    pub update_available: bool,
+    pub release_notes: Option<String>,
+}
+
+/// Newest first, as the GitHub releases API returns them.
+pub async fn list_versions(
+    endpoints: &ReleaseEndpoints,
+    channel: ReleaseChannel,
+) -> Result<Vec<YtDlpVersion>, String> {
+    let url = format!(
+        "{}/repos/{}/releases?per_page={VERSION_LIST_SIZE}",
+        endpoints.api_base.trim_end_matches('/'),
+        channel.repository()
+    );
+
+    fetch_json(&url, "yt-dlp 版本列表").await
+}
+
+/// Compares `current` with the release `release` would install: the channel's
+/// newest build, or the pinned tag. Nothing installed means nothing to update.
+pub async fn check_update(
+    endpoints: &ReleaseEndpoints,
+    release: &YtDlpRelease,
+    current: Option<&str>,
+) -> Result<UpdateCheck, String> {
+    let api_base = endpoints.api_base.trim_end_matches('/');
+    let repository = release.channel.repository();
+    let url = match &release.tag {
+        Some(tag) => format!("{api_base}/repos/{repository}/releases/tags/{tag}"),
+        None => format!("{api_base}/repos/{repository}/releases/latest"),
+    };
+    let target: YtDlpVersion = fetch_json(&url, "yt-dlp 最新版本").await?;
+
+    let update_available = current.is_some_and(|current| match release.tag {
+        // A pin may point at an older build, so any difference means reinstall.
+        Some(_) => compare_versions(current, &target.tag) != Ordering::Equal,
+        None => compare_versions(current, &target.tag) == Ordering::Less,
+    });
+
+    Ok(UpdateCheck {
+        current: current.map(str::to_string),
+        latest: target.tag,
+        update_available,
+        release_notes: target.release_notes,
+    })
+}
+
+/// Orders yt-dlp's calendar versions (`2024.08.06`, `2024.08.06.232703`)
+/// field by field numerically, so `2024.10.7` sorts after `2024.9.30`. A
+/// missing field counts as zero, which puts a nightly after its stable base.
+pub fn compare_versions(left: &str, right: &str) -> Ordering {
+    let fields = |version: &str| -> Vec<u64> {
+        version
+            .trim()
+            .trim_start_matches(|ch: char| !ch.is_ascii_digit())
+            .split(['.', '-'])
+            .map(|field| {
+                let digits: String = field.chars().take_while(char::is_ascii_digit).collect();
+                digits.parse().unwrap_or(0)
+            })
+            .collect()
+    };
+
+    let (left, right) = (fields(left), fields(right));
+    let len = left.len().max(right.len());
+    (0..len)
+        .map(|index| {
+            let left = left.get(index).copied().unwrap_or(0);
+            let right = right.get(index).copied().unwrap_or(0);
+            left.cmp(&right)
+        })
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+async fn fetch_json<T: DeserializeOwned>(url: &str, what: &str) -> Result<T, String> {
+    let response = reqwest::Client::new()
+        .get(url)
+        .header("User-Agent", "yt-dlp-x")
+        .header("Accept", "application/vnd.github+json")
+        .send()
+        .await
+        .map_err(|err| format!("获取{what}失败: {err}"))?;
+
+    if !response.status().is_success() {
+        return Err(format!("获取{what}失败，状态码: {}", response.status()));
+    }
+
+    let body = response
+        .bytes()
+        .await
+        .map_err(|err| format!("读取{what}失败: {err}"))?;
+    serde_json::from_slice(&body).map_err(|err| format!("解析{what}失败: {err}"))
+}
+
+/// Trims a user supplied tag; an empty tag means "no pin".
+pub fn normalize_tag(tag: Option<&str>) -> Result<Option<String>, String> {
+    let Some(tag) = tag.map(str::trim).filter(|tag| !tag.is_empty()) else {
+        return Ok(None);
+    };
+
+    let valid = tag
+        .chars()
+        .all(|ch| ch.is_ascii_alphanumeric() || matches!(ch, '.' | '-' | '_'));
+    if !valid || tag.starts_with('.') {
+        return Err(format!("无效的 yt-dlp 版本号: {tag}"));
+    }
+
+    Ok(Some(tag.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        check_update, compare_versions, list_versions, normalize_tag, ReleaseChannel,
+        ReleaseEndpoints, YtDlpRelease,
+    };
+    use crate::test_support::{Route, TestServer};
+    use std::cmp::Ordering;
+
+    fn endpoints(server: &TestServer) -> ReleaseEndpoints {
+        ReleaseEndpoints {
+            download_base: server.base_url.clone(),
+            api_base: server.base_url.clone(),
+        }
+    }
+
+    #[test]
+    fn builds_channel_and_pinned_urls() {
+        let endpoints = ReleaseEndpoints::default();
+        let stable = YtDlpRelease::default();
+        assert_eq!(
+            stable.binary_url(&endpoints, "yt-dlp"),
+            "https://github.com/yt-dlp/yt-dlp/releases/latest/download/yt-dlp"
+        );
+
+        let nightly = YtDlpRelease {
+            channel: ReleaseChannel::Nightly,
+            tag: None,
+        };
+        assert_eq!(
+            nightly.checksums_url(&endpoints),
+            "https://github.com/yt-dlp/yt-dlp-nightly-builds/releases/latest/download/SHA2-256SUMS"
+        );
+
+        let pinned = YtDlpRelease {
+            channel: ReleaseChannel::Master,
+            tag: Some("2025.01.02.123456".into()),
+        };
+        assert_eq!(
+            pinned.binary_url(&endpoints, "yt-dlp.exe"),
+            "https://github.com/yt-dlp/yt-dlp-master-builds/releases/download/2025.01.02.123456/yt-dlp.exe"
+        );
+    }
+
+    #[test]
+    fn normalizes_pinned_tags() {
+        assert_eq!(normalize_tag(None), Ok(None));
+        assert_eq!(normalize_tag(Some("  ")), Ok(None));
+        assert_eq!(
+            normalize_tag(Some(" 2025.01.02 ")),
+            Ok(Some("2025.01.02".into()))
+        );
+        assert!(normalize_tag(Some("../latest")).is_err());
+        assert!(normalize_tag(Some("2025 01")).is_err());
+    }
+
+    #[tokio::test]
+    async fn lists_versions_from_the_release_api() {
+        let server = TestServer::start(vec![Route::ok(
+            "/repos/yt-dlp/yt-dlp-nightly-builds/releases",
+            r#"[
+                {"tag_name": "2025.02.01.000001", "name": "yt-dlp nightly 2025.02.01.000001", "published_at": "2025-02-01T00:00:01Z", "prerelease": false, "assets": []},
+                {"tag_name": "2025.01.31.000001", "name": null, "published_at": "2025-01-31T00:00:01Z"}
+            ]"#,
+        )]);
+        let endpoints = endpoints(&server);
+
+        let versions = list_versions(&endpoints, ReleaseChannel::Nightly)
+            .await
+            .expect("versions should load");
+        assert_eq!(versions.len(), 2);
+        assert_eq!(versions[0].tag, "2025.02.01.000001");
+        assert_eq!(
+            versions[0].published_at.as_deref(),
+            Some("2025-02-01T00:00:01Z")
+        );
+        assert_eq!(versions[1].name, None);
+
+        assert!(list_versions(&endpoints, ReleaseChannel::Master)
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn orders_calendar_versions_numerically() {
+        let cases = [
+            ("2024.08.06", "2024.08.06", Ordering::Equal),
+            ("2024.9.30", "2024.10.07", Ordering::Less),
+            ("2024.08.06", "2024.08.06.232703", Ordering::Less),
+            ("2025.01.02.000001", "2025.01.02", Ordering::Greater),
+            ("2023.12.30", "2024.01.01", Ordering::Less),
+            ("stable@2024.08.06", "2024.08.06", Ordering::Equal),
+        ];
+
+        for (left, right, expected) in cases {
+            assert_eq!(compare_versions(left, right), expected, "{left} vs {right}");
+        }
+    }
+
+    #[tokio::test]
+    async fn checks_for_updates_against_latest_and_pinned_releases() {
+        let server = TestServer::start(vec![
+            Route::ok(
+                "/repos/yt-dlp/yt-dlp/releases/latest",
+                r#"{"tag_name": "2025.03.01", "body": "Fixed YouTube"}"#,
+            ),
+            Route::ok(
+                "/repos/yt-dlp/yt-dlp/releases/tags/2024.12.01",
+                r#"{"tag_name": "2024.12.01", "body": null}"#,
+            ),
+        ]);
+        let endpoints = endpoints(&server);
+        let latest = YtDlpRelease::default();
+
+        let check = check_update(&endpoints, &latest, Some("2025.02.10"))
+            .await
+            .expect("check should succeed");
+        assert_eq!(check.latest, "2025.03.01");
+        assert!(check.update_available);
+        assert_eq!(check.release_notes.as_deref(), Some("Fixed YouTube"));
+
+        let check = check_update(&endpoints, &latest, Some("2025.03.01"))
+            .await
+            .expect("check should succeed");
+        assert!(!check.update_available);
+
+        let check = check_update(&endpoints, &latest, None)
+            .await
+            .expect("check should succeed");
+        assert!(!check.update_available);
+
+        let pinned = YtDlpRelease {
+            channel: ReleaseChannel::Stable,
+            tag: Some("2024.12.01".into()),
+        };
+        let check = check_update(&endpoints, &pinned, Some("2025.03.01"))
+            .await
+            .expect("check should succeed");
+        assert_eq!(check.latest, "2024.12.01");
+        assert!(check.update_available);
+    }
+}
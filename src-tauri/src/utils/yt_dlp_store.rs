@@ -0,0 +1,250 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use super::storage;
+
+const MANIFEST_FILE: &str = "versions.json";
+const VERSIONS_DIR: &str = "versions";
+/// Installed versions kept around for rollback, the active one included.
+const KEEP_INSTALLED: usize = 3;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InstalledBinary {
+    version: String,
+    sha256: String,
+    installed_at: u64,
+}
+
+/// Oldest install first; `active` is the version downloads run with.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct Manifest {
+    active: Option<String>,
+    installed: Vec<InstalledBinary>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstalledYtDlp {
+    pub version: String,
+    pub sha256: String,
+    pub installed_at: u64,
+    pub path: String,
+    pub active: bool,
+}
+
+/// Bundled yt-dlp binaries kept side by side as `versions/<version>/<asset>`
+/// so a bad release can be rolled back without downloading again.
+pub struct BinaryStore {
+    bin_dir: PathBuf,
+    asset_name: &'static str,
+}
+
+impl BinaryStore {
+    pub fn new(bin_dir: PathBuf, asset_name: &'static str) -> Self {
+        Self {
+            bin_dir,
+            asset_name,
+        }
+    }
+
+    pub fn active_path(&self) -> Result<Option<PathBuf>, String> {
+        let manifest = self.load()?;
+        Ok(manifest
+            .active
+            .map(|version| self.binary_path(&version))
+            .filter(|path| path.exists()))
+    }
+
+    /// Moves a downloaded and validated binary into place, makes it active and
+    /// drops the oldest versions beyond the rollback limit.
+    pub fn add(
+        &self,
+        version: &str,
+        sha256: &str,
+        staged_path: &Path,
+        installed_at: u64,
+    ) -> Result<PathBuf, String> {
+        let target = self.binary_path(version);
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent).map_err(|err| format!("创建目录失败: {err}"))?;
+        }
+        if target.exists() {
+            std::fs::remove_file(&target).map_err(|err| format!("替换 yt-dlp 文件失败: {err}"))?;
+        }
+        std::fs::rename(staged_path, &target)
+            .map_err(|err| format!("替换 yt-dlp 文件失败: {err}"))?;
+
+        let mut manifest = self.load()?;
+        manifest.installed.retain(|entry| entry.version != version);
+        manifest.installed.push(InstalledBinary {
+            version: version.to_string(),
+            sha256: sha256.to_string(),
+            installed_at,
+        });
+        manifest.active = Some(version.to_string());
+        self.prune(&mut manifest);
+        self.save(&manifest)?;
+
+        Ok(target)
+    }
+
+    pub fn activate(&self, version: &str) -> Result<PathBuf, String> {
+        let mut manifest = self.load()?;
+        let path = self.binary_path(version);
+        if !manifest
+            .installed
+            .iter()
+            .any(|entry| entry.version == version)
+            || !path.exists()
+        {
+            return Err(format!("未安装 yt-dlp {version}"));
+        }
+
+        manifest.active = Some(version.to_string());
+        self.save(&manifest)?;
+        Ok(path)
+    }
+
+    /// Newest install first.
+    pub fn list(&self) -> Result<Vec<InstalledYtDlp>, String> {
+        let manifest = self.load()?;
+        Ok(manifest
+            .installed
+            .iter()
+            .rev()
+            .map(|entry| InstalledYtDlp {
+                version: entry.version.clone(),
+                sha256: entry.sha256.clone(),
+                installed_at: entry.installed_at,
+                path: self
+                    .binary_path(&entry.version)
+                    .to_string_lossy()
+                    .to_string(),
+                active: manifest.active.as_deref() == Some(entry.version.as_str()),
+            })
+            .collect())
+    }
+
+    fn prune(&self, manifest: &mut Manifest) {
+        while manifest.installed.len() > KEEP_INSTALLED {
+            let Some(index) = manifest
+                .installed
+                .iter()
+                .position(|entry| manifest.active.as_deref() != Some(entry.version.as_str()))
+            else {
+                break;
+            };
+            let removed = manifest.installed.remove(index);
+            // Best effort: a binary still running a download cannot be removed on Windows.
+            if let Some(dir) = self.binary_path(&removed.version).parent() {
+                let _ = std::fs::remove_dir_all(dir);
+            }
+        }
+    }
+
+    fn binary_path(&self, version: &str) -> PathBuf {
+        self.bin_dir
+            .join(VERSIONS_DIR)
+            .join(version)
+            .join(self.asset_name)
+    }
+
+    fn load(&self) -> Result<Manifest, String> {
+        Ok(storage::load_json(&self.bin_dir.join(MANIFEST_FILE))?.unwrap_or_default())
+    }
+
+    fn save(&self, manifest: &Manifest) -> Result<(), String> {
+        storage::save_json(&self.bin_dir.join(MANIFEST_FILE), manifest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BinaryStore;
+    use std::path::{Path, PathBuf};
+
+    fn store(name: &str) -> (BinaryStore, PathBuf) {
+        let dir =
+            std::env::temp_dir().join(format!("yt-dlp-x-store-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).expect("create dir");
+        (BinaryStore::new(dir.clone(), "yt-dlp"), dir)
+    }
+
+    fn cleanup(dir: &Path) {
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    fn install(store: &BinaryStore, dir: &Path, version: &str, installed_at: u64) -> PathBuf {
+        let staged = dir.join("staged");
+        std::fs::write(&staged, version).expect("write staged binary");
+        store
+            .add(version, &format!("sha-{version}"), &staged, installed_at)
+            .expect("add version")
+    }
+
+    #[test]
+    fn keeps_recent_versions_and_activates_the_newest() {
+        let (store, dir) = store("keep");
+        for (index, version) in ["2025.01.01", "2025.02.01", "2025.03.01", "2025.04.01"]
+            .into_iter()
+            .enumerate()
+        {
+            install(&store, &dir, version, index as u64);
+        }
+
+        let installed = store.list().expect("list");
+        let versions: Vec<_> = installed
+            .iter()
+            .map(|entry| entry.version.as_str())
+            .collect();
+        assert_eq!(versions, ["2025.04.01", "2025.03.01", "2025.02.01"]);
+        assert!(installed[0].active);
+        assert!(!dir.join("versions").join("2025.01.01").exists());
+
+        let active = store.active_path().expect("active").expect("has active");
+        assert_eq!(std::fs::read_to_string(active).expect("read"), "2025.04.01");
+
+        cleanup(&dir);
+    }
+
+    #[test]
+    fn rolls_back_to_an_installed_version() {
+        let (store, dir) = store("rollback");
+        install(&store, &dir, "2025.01.01", 1);
+        install(&store, &dir, "2025.02.01", 2);
+
+        let path = store.activate("2025.01.01").expect("activate");
+        assert_eq!(store.active_path().expect("active"), Some(path));
+        assert!(store.activate("2024.12.01").is_err());
+
+        let active: Vec<_> = store
+            .list()
+            .expect("list")
+            .into_iter()
+            .filter(|entry| entry.active)
+            .map(|entry| entry.version)
+            .collect();
+        assert_eq!(active, ["2025.01.01"]);
+
+        cleanup(&dir);
+    }
+
+    #[test]
+    fn reinstalling_a_version_replaces_it() {
+        let (store, dir) = store("reinstall");
+        install(&store, &dir, "2025.01.01", 1);
+        install(&store, &dir, "2025.02.01", 2);
+        install(&store, &dir, "2025.01.01", 3);
+
+        let installed = store.list().expect("list");
+        assert_eq!(installed.len(), 2);
+        assert_eq!(installed[0].version, "2025.01.01");
+        assert_eq!(installed[0].installed_at, 3);
+        assert!(installed[0].active);
+
+        cleanup(&dir);
+    }
+}
@@ -1,8 +1,11 @@
+use serde::{Deserialize, Serialize};
 use std::path::Path;
 
 use crate::{
-    utils::yt_dlp::RuntimeCapabilities,
-    yt_dlp_progress::progress_template_value,
+    sponsorblock,
+    utils::{ffmpeg_features::FfmpegRequirement, yt_dlp::RuntimeCapabilities},
+    yt_dlp_progress::{downloaded_item_print_template, progress_template_value},
+    yt_dlp_sections::{section_specs, SectionOptions},
 };
 
 const DOUYIN_REFERER: &str = "https://www.douyin.com/";
@@ -28,6 +31,376 @@ pub enum VideoQualityArg {
     Highest,
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlaylistOrder {
+    #[default]
+    Default,
+    Reverse,
+    Random,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaylistOptions {
+    /// yt-dlp `--playlist-items` spec, e.g. `1-5,8,-3:`.
+    pub items: Option<String>,
+    #[serde(default)]
+    pub order: PlaylistOrder,
+    pub max_count: Option<u32>,
+    /// Folder template placed in front of the filename template, e.g. `%(playlist_title)s`.
+    pub subfolder_template: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VideoCodecPreference {
+    Av1,
+    Vp9,
+    H264,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioCodecPreference {
+    Opus,
+    Aac,
+    Mp3,
+}
+
+impl VideoCodecPreference {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Av1 => "AV1",
+            Self::Vp9 => "VP9",
+            Self::H264 => "H.264",
+        }
+    }
+}
+
+impl AudioCodecPreference {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Opus => "Opus",
+            Self::Aac => "AAC",
+            Self::Mp3 => "MP3",
+        }
+    }
+}
+
+/// Structured replacement for the quality presets. Either pins exact format
+/// ids from a probe, or narrows the candidates with constraints and codec
+/// preferences; the two styles cannot be mixed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct FormatSelection {
+    pub format_ids: Vec<String>,
+    pub max_height: Option<u32>,
+    pub video_codec: Option<VideoCodecPreference>,
+    pub audio_codec: Option<AudioCodecPreference>,
+    pub max_fps: Option<u32>,
+    pub hdr: Option<bool>,
+    pub max_filesize_mb: Option<u32>,
+}
+
+impl FormatSelection {
+    /// A selection without ids or constraints leaves the quality preset alone.
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioFormat {
+    #[default]
+    Mp3,
+    M4a,
+    Aac,
+    Opus,
+    Flac,
+    Wav,
+    /// Extract the source audio stream without re-encoding.
+    Original,
+}
+
+impl AudioFormat {
+    fn yt_dlp_value(self) -> &'static str {
+        match self {
+            Self::Mp3 => "mp3",
+            Self::M4a => "m4a",
+            Self::Aac => "aac",
+            Self::Opus => "opus",
+            Self::Flac => "flac",
+            Self::Wav => "wav",
+            Self::Original => "best",
+        }
+    }
+
+    fn is_lossy_encode(self) -> bool {
+        matches!(self, Self::Mp3 | Self::M4a | Self::Aac | Self::Opus)
+    }
+
+    /// The encoder and muxer yt-dlp's audio extraction runs through ffmpeg.
+    fn ffmpeg_requirements(self) -> Vec<FfmpegRequirement> {
+        let (encoders, muxer, output): (&'static [&'static str], _, _) = match self {
+            Self::Mp3 => (&["libmp3lame"], "mp3", "MP3 音频"),
+            Self::M4a => (&["aac", "libfdk_aac"], "ipod", "M4A 音频"),
+            Self::Aac => (&["aac", "libfdk_aac"], "adts", "AAC 音频"),
+            Self::Opus => (&["libopus"], "opus", "Opus 音频"),
+            Self::Flac => (&["flac"], "flac", "FLAC 音频"),
+            Self::Wav => (&["pcm_s16le"], "wav", "WAV 音频"),
+            Self::Original => return Vec::new(),
+        };
+
+        vec![
+            FfmpegRequirement::Encoder {
+                any_of: encoders,
+                output,
+            },
+            FfmpegRequirement::Muxer {
+                name: muxer,
+                output,
+            },
+        ]
+    }
+
+    /// Raw ADTS and WAV files have no tag container for cover art. The source
+    /// container of `Original` is unknown up front, so it is opt-in only.
+    fn embeds_thumbnail_by_default(self) -> bool {
+        !matches!(self, Self::Aac | Self::Wav | Self::Original)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AudioOptions {
+    pub format: AudioFormat,
+    /// `--audio-quality` value: VBR level `0`-`10` (0 is best) or a bitrate such as `192K`.
+    pub quality: Option<String>,
+    /// Defaults to embedding whenever the output format can hold cover art.
+    pub embed_thumbnail: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VideoContainer {
+    #[default]
+    Mp4,
+    Mkv,
+    Webm,
+    Mov,
+}
+
+impl VideoContainer {
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Mp4 => "mp4",
+            Self::Mkv => "mkv",
+            Self::Webm => "webm",
+            Self::Mov => "mov",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Mp4 => "MP4",
+            Self::Mkv => "MKV",
+            Self::Webm => "WebM",
+            Self::Mov => "MOV",
+        }
+    }
+
+    fn supports_video_codec(self, codec: VideoCodecPreference) -> bool {
+        match self {
+            Self::Mp4 | Self::Mkv => true,
+            Self::Webm => codec != VideoCodecPreference::H264,
+            Self::Mov => codec == VideoCodecPreference::H264,
+        }
+    }
+
+    fn supports_audio_codec(self, codec: AudioCodecPreference) -> bool {
+        match self {
+            Self::Mkv => true,
+            Self::Webm => codec == AudioCodecPreference::Opus,
+            Self::Mp4 | Self::Mov => codec != AudioCodecPreference::Opus,
+        }
+    }
+
+    /// `-f` filters that keep stream copies to codecs the container can hold.
+    /// `none` lets video-only and audio-only streams through, and `~=?` keeps
+    /// streams whose codec the extractor did not report.
+    fn stream_filter(self) -> Option<&'static str> {
+        match self {
+            Self::Mp4 => Some("[acodec~=?'^(mp4a|aac|mp3|none)']"),
+            Self::Mkv => None,
+            Self::Webm => {
+                Some("[vcodec~=?'^(vp0?[89]|av01|none)'][acodec~=?'^(opus|vorbis|none)']")
+            }
+            Self::Mov => Some("[vcodec~=?'^(avc|h264|none)'][acodec~=?'^(mp4a|aac|mp3|none)']"),
+        }
+    }
+
+    fn ffmpeg_muxer(self) -> FfmpegRequirement {
+        let (name, output) = match self {
+            Self::Mp4 => ("mp4", "MP4 视频"),
+            Self::Mkv => ("matroska", "MKV 视频"),
+            Self::Webm => ("webm", "WebM 视频"),
+            Self::Mov => ("mov", "MOV 视频"),
+        };
+        FfmpegRequirement::Muxer { name, output }
+    }
+}
+
+/// How the downloaded streams end up in the chosen container.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContainerConversion {
+    /// Only applies when separate video and audio streams are merged.
+    #[default]
+    Merge,
+    /// Rewraps single-file downloads too, without touching the streams.
+    Remux,
+    /// Re-encodes into the container's codecs; slow but always compatible.
+    Recode,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct VideoOutputOptions {
+    pub container: VideoContainer,
+    pub conversion: ContainerConversion,
+}
+
+impl VideoOutputOptions {
+    pub fn requires_ffmpeg(&self) -> bool {
+        self.conversion != ContainerConversion::Merge
+    }
+
+    /// Merging and remuxing copy the streams as they are, so they are filtered
+    /// down to what the container can hold; only a recode converts them.
+    fn stream_filter(&self) -> Option<&'static str> {
+        match self.conversion {
+            ContainerConversion::Merge | ContainerConversion::Remux => {
+                self.container.stream_filter()
+            }
+            ContainerConversion::Recode => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SubtitleSource {
+    #[default]
+    Manual,
+    Auto,
+    Both,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SubtitleFormat {
+    #[default]
+    Srt,
+    Vtt,
+    Ass,
+}
+
+impl SubtitleFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Srt => "srt",
+            Self::Vtt => "vtt",
+            Self::Ass => "ass",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SubtitleOptions {
+    /// yt-dlp `--sub-langs` entries, e.g. `en`, `zh-Hans`, `en.*` or `all`.
+    pub languages: Vec<String>,
+    pub source: SubtitleSource,
+    pub format: SubtitleFormat,
+    /// Embed into the video container instead of writing sidecar files.
+    pub embed: bool,
+}
+
+/// Extra files and tags for video downloads; audio mode handles its cover art
+/// through [`AudioOptions`] instead.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct VideoEmbedOptions {
+    pub metadata: bool,
+    pub chapters: bool,
+    pub thumbnail: bool,
+    /// Attaches the info JSON to the file; only MKV can carry attachments.
+    pub info_json: bool,
+    pub write_description: bool,
+    pub write_info_json: bool,
+}
+
+impl VideoEmbedOptions {
+    pub fn requires_ffmpeg(&self) -> bool {
+        self.metadata || self.chapters || self.thumbnail || self.info_json
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SponsorBlockCategory {
+    Sponsor,
+    Intro,
+    Outro,
+    Selfpromo,
+    Preview,
+    Filler,
+    Interaction,
+    MusicOfftopic,
+    /// Mark-only: a single highlight point rather than a segment.
+    PoiHighlight,
+    /// Mark-only: chapter titles submitted to SponsorBlock.
+    Chapter,
+    All,
+}
+
+impl SponsorBlockCategory {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Sponsor => "sponsor",
+            Self::Intro => "intro",
+            Self::Outro => "outro",
+            Self::Selfpromo => "selfpromo",
+            Self::Preview => "preview",
+            Self::Filler => "filler",
+            Self::Interaction => "interaction",
+            Self::MusicOfftopic => "music_offtopic",
+            Self::PoiHighlight => "poi_highlight",
+            Self::Chapter => "chapter",
+            Self::All => "all",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SponsorBlockOptions {
+    /// Categories written as chapters.
+    pub mark: Vec<SponsorBlockCategory>,
+    /// Categories cut out of the file.
+    pub remove: Vec<SponsorBlockCategory>,
+    /// Custom SponsorBlock server; yt-dlp uses `https://sponsor.ajay.app` when unset.
+    pub api_url: Option<String>,
+}
+
+impl SponsorBlockOptions {
+    pub fn requires_ffmpeg(&self) -> bool {
+        !self.mark.is_empty() || !self.remove.is_empty()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct DownloadTuning {
     pub retries: u32,
@@ -89,9 +462,37 @@ pub struct BuildYtDlpArgsInput<'a> {
     pub ffmpeg_path: Option<&'a Path>,
     pub runtime_caps: &'a RuntimeCapabilities,
     pub tuning: DownloadTuning,
+    pub playlist: Option<&'a PlaylistOptions>,
+    pub format: Option<&'a FormatSelection>,
+    pub audio: Option<&'a AudioOptions>,
+    pub video_output: Option<&'a VideoOutputOptions>,
+    pub subtitles: Option<&'a SubtitleOptions>,
+    pub embed: Option<&'a VideoEmbedOptions>,
+    pub sponsorblock: Option<&'a SponsorBlockOptions>,
+    pub sections: Option<&'a SectionOptions>,
+    pub download_archive: Option<&'a Path>,
+}
+
+/// What the ffmpeg build must support to produce the requested output. Video
+/// encoders are not listed: a recode falls back to whatever encoder ffmpeg
+/// picks for the container.
+pub fn ffmpeg_requirements(
+    mode: DownloadModeArg,
+    audio: Option<&AudioOptions>,
+    video_output: Option<&VideoOutputOptions>,
+) -> Vec<FfmpegRequirement> {
+    match mode {
+        DownloadModeArg::Audio => audio
+            .map(|options| options.format)
+            .unwrap_or_default()
+            .ffmpeg_requirements(),
+        DownloadModeArg::Video => video_output
+            .map(|options| vec![options.container.ffmpeg_muxer()])
+            .unwrap_or_default(),
+    }
 }
 
-pub fn build_yt_dlp_args(input: BuildYtDlpArgsInput<'_>) -> Vec<String> {
+pub fn build_yt_dlp_args(input: BuildYtDlpArgsInput<'_>) -> Result<Vec<String>, String> {
     let BuildYtDlpArgsInput {
         url,
         mode,
@@ -102,15 +503,45 @@ pub fn build_yt_dlp_args(input: BuildYtDlpArgsInput<'_>) -> Vec<String> {
         ffmpeg_path,
         runtime_caps,
         tuning,
+        playlist,
+        format,
+        audio,
+        video_output,
+        subtitles,
+        embed,
+        sponsorblock,
+        sections,
+        download_archive,
     } = input;
 
+    let compiled_format = format
+        .filter(|selection| !selection.is_empty())
+        .map(|selection| compile_format_selection(selection, mode))
+        .transpose()?;
+
+    let filename_template = match sections {
+        Some(options) if options.may_write_several_files() => {
+            number_section_files(tuning.filename_template)
+        }
+        _ => tuning.filename_template,
+    };
+
+    let output_template = match playlist.and_then(playlist_subfolder) {
+        Some(subfolder) => format!("{subfolder}/{filename_template}"),
+        None => filename_template,
+    };
+
     let mut args: Vec<String> = vec![
         "--newline".into(),
-        "--no-playlist".into(),
+        if playlist.is_some() {
+            "--yes-playlist".into()
+        } else {
+            "--no-playlist".into()
+        },
         "--continue".into(),
         "--no-mtime".into(),
         "-o".into(),
-        tuning.filename_template,
+        output_template,
         "-P".into(),
         format!("home:{}", output_dir.to_string_lossy()),
         "-R".into(),
@@ -138,6 +569,628 @@ pub fn build_yt_dlp_args(input: BuildYtDlpArgsInput<'_>) -> Vec<String> {
         args.push(progress_template_value().into());
     }
 
+    // `--print` implies `--quiet`, which would swallow progress and playlist
+    // markers, so only report finished items where it can be switched back off.
+    if runtime_caps.supports_no_quiet {
+        args.push("--print".into());
+        args.push(downloaded_item_print_template().into());
+        args.push("--no-quiet".into());
+    }
+
+    apply_browser_cookies(&mut args, browser);
+
+    if let Some(archive) = download_archive {
+        args.push("--download-archive".into());
+        args.push(archive.to_string_lossy().to_string());
+    }
+
+    if let Some(playlist) = playlist {
+        apply_playlist_options(&mut args, playlist)?;
+    }
+
+    match mode {
+        DownloadModeArg::Audio => {
+            if embed.is_some() {
+                return Err("音频模式请使用音频选项中的封面嵌入".into());
+            }
+            push_format_args(&mut args, compiled_format, || "bestaudio/best".into());
+            args.push("-x".into());
+            apply_audio_options(&mut args, audio.cloned().unwrap_or_default())?;
+        }
+        DownloadModeArg::Video => {
+            // Pinned format IDs are taken as given: filtering them could only turn
+            // the user's explicit choice into "Requested format is not available".
+            let pinned = format.is_some_and(|selection| !selection.format_ids.is_empty());
+            let stream_filter = video_output
+                .and_then(VideoOutputOptions::stream_filter)
+                .filter(|_| !pinned);
+            let compiled_format = compiled_format.map(|compiled| CompiledFormat {
+                format: constrain_streams(&compiled.format, stream_filter),
+                sort: compiled.sort,
+            });
+            push_format_args(&mut args, compiled_format, || {
+                constrain_streams(&video_format_for_quality(quality, url), stream_filter)
+            });
+            apply_video_output_options(&mut args, video_output, format)?;
+            if let Some(embed) = embed {
+                let container = video_output
+                    .map(|options| options.container)
+                    .unwrap_or_default();
+                apply_video_embed_options(
+                    &mut args,
+                    embed,
+                    container,
+                    runtime_caps,
+                    ffmpeg_path.is_some(),
+                )?;
+            }
+        }
+    }
+
+    if let Some(subtitles) = subtitles {
+        let container = video_output.map(|options| options.container);
+        apply_subtitle_options(&mut args, subtitles, mode, container)?;
+    }
+
+    if let Some(sponsorblock) = sponsorblock {
+        apply_sponsorblock_options(&mut args, sponsorblock, runtime_caps, ffmpeg_path.is_some())?;
+    }
+
+    if let Some(sections) = sections.filter(|options| options.requires_ffmpeg()) {
+        if ffmpeg_path.is_none() {
+            return Err("按片段下载需要 ffmpeg，请先安装 ffmpeg".into());
+        }
+        for spec in section_specs(sections)? {
+            args.push("--download-sections".into());
+            args.push(spec);
+        }
+        if sections.force_keyframes_at_cuts {
+            args.push("--force-keyframes-at-cuts".into());
+        }
+    }
+
+    if let Some(path) = ffmpeg_path {
+        args.push("--ffmpeg-location".into());
+        args.push(path.to_string_lossy().to_string());
+    }
+
+    apply_site_specific_overrides(&mut args, url, runtime_caps);
+
+    args.push(url.to_string());
+    Ok(args)
+}
+
+/// Arguments for a metadata-only run that prints the info JSON without
+/// downloading. Playlists are listed flat so probing a channel stays cheap.
+pub fn build_probe_args(
+    url: &str,
+    browser: Option<&str>,
+    runtime_caps: &RuntimeCapabilities,
+) -> Vec<String> {
+    let mut args: Vec<String> = vec![
+        "--dump-single-json".into(),
+        "--no-warnings".into(),
+        "--no-playlist".into(),
+        "--flat-playlist".into(),
+    ];
+
+    apply_browser_cookies(&mut args, browser);
+    apply_site_specific_overrides(&mut args, url, runtime_caps);
+
+    args.push(url.to_string());
+    args
+}
+
+/// Prints the `extractor id` pair yt-dlp would write to a download archive, one
+/// line per entry when `playlist` is set and the URL is a playlist.
+pub fn build_archive_key_args(
+    url: &str,
+    browser: Option<&str>,
+    playlist: bool,
+    runtime_caps: &RuntimeCapabilities,
+) -> Vec<String> {
+    let mut args: Vec<String> = vec![
+        "--simulate".into(),
+        "--no-warnings".into(),
+        if playlist {
+            "--yes-playlist".into()
+        } else {
+            "--no-playlist".into()
+        },
+        "--flat-playlist".into(),
+        "--print".into(),
+        "%(extractor_key,ie_key)s %(id)s".into(),
+    ];
+
+    apply_browser_cookies(&mut args, browser);
+    apply_site_specific_overrides(&mut args, url, runtime_caps);
+
+    args.push(url.to_string());
+    args
+}
+
+fn apply_audio_options(args: &mut Vec<String>, options: AudioOptions) -> Result<(), String> {
+    let format = options.format;
+    args.push("--audio-format".into());
+    args.push(format.yt_dlp_value().into());
+
+    if let Some(quality) = options
+        .quality
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+    {
+        if !format.is_lossy_encode() {
+            return Err("无损或保留原始音频时不能设置音频质量".into());
+        }
+        args.push("--audio-quality".into());
+        args.push(normalize_audio_quality(quality)?);
+    }
+
+    let embed_thumbnail = match options.embed_thumbnail {
+        Some(true) if matches!(format, AudioFormat::Aac | AudioFormat::Wav) => {
+            return Err(format!(
+                "{} 格式不支持嵌入封面",
+                format.yt_dlp_value().to_uppercase()
+            ));
+        }
+        Some(embed) => embed,
+        None => format.embeds_thumbnail_by_default(),
+    };
+
+    if embed_thumbnail {
+        args.push("--embed-thumbnail".into());
+        args.push("--convert-thumbnails".into());
+        args.push("jpg".into());
+    }
+
+    Ok(())
+}
+
+fn normalize_audio_quality(quality: &str) -> Result<String, String> {
+    if let Ok(level) = quality.parse::<u8>() {
+        if level <= 10 {
+            return Ok(level.to_string());
+        }
+    }
+
+    let bitrate = quality
+        .strip_suffix(['k', 'K'])
+        .and_then(|value| value.parse::<u32>().ok())
+        .filter(|value| (8..=512).contains(value));
+
+    bitrate
+        .map(|value| format!("{value}K"))
+        .ok_or_else(|| format!("无效的音频质量: {quality}，请填写 0-10 或 8K-512K"))
+}
+
+fn apply_video_output_options(
+    args: &mut Vec<String>,
+    options: Option<&VideoOutputOptions>,
+    format: Option<&FormatSelection>,
+) -> Result<(), String> {
+    // Codec checks only apply to an explicitly chosen container; the implicit
+    // mp4 default keeps whatever the format selection yields, as before.
+    let checked = options.is_some();
+    let options = options.cloned().unwrap_or_default();
+    let container = options.container;
+
+    // Recoding picks codecs that fit the container, so only stream copies can
+    // clash. The `-f` filters already exclude such streams; rejecting a
+    // contradictory codec preference up front gives a clearer error.
+    if checked && options.conversion != ContainerConversion::Recode {
+        if let Some(codec) = format
+            .and_then(|selection| selection.video_codec)
+            .filter(|codec| !container.supports_video_codec(*codec))
+        {
+            return Err(format!(
+                "{} 容器不支持 {} 视频编码，请更换容器或选择重新编码",
+                container.label(),
+                codec.label()
+            ));
+        }
+        if let Some(codec) = format
+            .and_then(|selection| selection.audio_codec)
+            .filter(|codec| !container.supports_audio_codec(*codec))
+        {
+            return Err(format!(
+                "{} 容器不支持 {} 音频编码，请更换容器或选择重新编码",
+                container.label(),
+                codec.label()
+            ));
+        }
+    }
+
+    args.push("--merge-output-format".into());
+    args.push(container.extension().into());
+
+    match options.conversion {
+        ContainerConversion::Merge => {}
+        ContainerConversion::Remux => {
+            args.push("--remux-video".into());
+            args.push(container.extension().into());
+        }
+        ContainerConversion::Recode => {
+            args.push("--recode-video".into());
+            args.push(container.extension().into());
+        }
+    }
+
+    Ok(())
+}
+
+fn apply_video_embed_options(
+    args: &mut Vec<String>,
+    options: &VideoEmbedOptions,
+    container: VideoContainer,
+    runtime_caps: &RuntimeCapabilities,
+    has_ffmpeg: bool,
+) -> Result<(), String> {
+    if options.requires_ffmpeg() && !has_ffmpeg {
+        return Err("嵌入元数据、章节或封面需要 ffmpeg，请先安装 ffmpeg".into());
+    }
+
+    let unsupported = [
+        (
+            options.metadata,
+            runtime_caps.supports_embed_metadata,
+            "--embed-metadata",
+        ),
+        (
+            options.chapters,
+            runtime_caps.supports_embed_chapters,
+            "--embed-chapters",
+        ),
+        (
+            options.info_json,
+            runtime_caps.supports_embed_info_json,
+            "--embed-info-json",
+        ),
+    ]
+    .into_iter()
+    .find(|(requested, supported, _)| *requested && !supported);
+    if let Some((_, _, flag)) = unsupported {
+        return Err(format!("当前 yt-dlp 版本不支持 {flag}，请更新 yt-dlp"));
+    }
+
+    if options.thumbnail && container == VideoContainer::Webm {
+        return Err("WebM 容器不支持嵌入封面".into());
+    }
+    if options.info_json && container != VideoContainer::Mkv {
+        return Err("只有 MKV 容器支持嵌入 info.json".into());
+    }
+
+    if options.metadata {
+        args.push("--embed-metadata".into());
+    }
+    if options.chapters {
+        args.push("--embed-chapters".into());
+    }
+    if options.thumbnail {
+        args.push("--embed-thumbnail".into());
+        args.push("--convert-thumbnails".into());
+        args.push("jpg".into());
+    }
+    if options.info_json {
+        args.push("--embed-info-json".into());
+    }
+    if options.write_description {
+        args.push("--write-description".into());
+    }
+    if options.write_info_json {
+        args.push("--write-info-json".into());
+    }
+
+    Ok(())
+}
+
+fn apply_sponsorblock_options(
+    args: &mut Vec<String>,
+    options: &SponsorBlockOptions,
+    runtime_caps: &RuntimeCapabilities,
+    has_ffmpeg: bool,
+) -> Result<(), String> {
+    if !options.requires_ffmpeg() {
+        return Ok(());
+    }
+    if !runtime_caps.supports_sponsorblock {
+        return Err("当前 yt-dlp 版本不支持 SponsorBlock，请更新 yt-dlp".into());
+    }
+    if !has_ffmpeg {
+        return Err("SponsorBlock 标记或移除片段需要 ffmpeg，请先安装 ffmpeg".into());
+    }
+    if let Some(category) = options.remove.iter().find(|category| {
+        matches!(
+            category,
+            SponsorBlockCategory::PoiHighlight | SponsorBlockCategory::Chapter
+        )
+    }) {
+        return Err(format!(
+            "SponsorBlock 分类 {} 只能标记，不能移除",
+            category.name()
+        ));
+    }
+    if let Some(category) = options
+        .mark
+        .iter()
+        .find(|category| options.remove.contains(category))
+    {
+        return Err(format!(
+            "SponsorBlock 分类 {} 不能同时标记和移除",
+            category.name()
+        ));
+    }
+
+    let join = |categories: &[SponsorBlockCategory]| {
+        categories
+            .iter()
+            .map(|category| category.name())
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+
+    if !options.mark.is_empty() {
+        args.push("--sponsorblock-mark".into());
+        args.push(join(&options.mark));
+    }
+    if !options.remove.is_empty() {
+        args.push("--sponsorblock-remove".into());
+        args.push(join(&options.remove));
+    }
+    if let Some(api_url) = options
+        .api_url
+        .as_deref()
+        .filter(|value| !value.trim().is_empty())
+    {
+        args.push("--sponsorblock-api".into());
+        args.push(sponsorblock::normalize_api_url(api_url)?);
+    }
+
+    Ok(())
+}
+
+fn apply_subtitle_options(
+    args: &mut Vec<String>,
+    options: &SubtitleOptions,
+    mode: DownloadModeArg,
+    container: Option<VideoContainer>,
+) -> Result<(), String> {
+    let languages: Vec<&str> = options
+        .languages
+        .iter()
+        .map(|language| language.trim())
+        .filter(|language| !language.is_empty())
+        .collect();
+
+    if languages.is_empty() {
+        return Err("请至少选择一种字幕语言".into());
+    }
+    if let Some(invalid) = languages.iter().find(|language| {
+        !language
+            .trim_start_matches('-')
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || matches!(ch, '-' | '_' | '.' | '*'))
+    }) {
+        return Err(format!("无效的字幕语言: {invalid}"));
+    }
+
+    if options.embed {
+        if mode == DownloadModeArg::Audio {
+            return Err("音频模式不支持嵌入字幕，请改为单独保存字幕文件".into());
+        }
+        // WebM can only carry WebVTT subtitle tracks.
+        if container == Some(VideoContainer::Webm) && options.format != SubtitleFormat::Vtt {
+            return Err("WebM 容器只能嵌入 VTT 字幕".into());
+        }
+    }
+
+    if matches!(
+        options.source,
+        SubtitleSource::Manual | SubtitleSource::Both
+    ) {
+        args.push("--write-subs".into());
+    }
+    if matches!(options.source, SubtitleSource::Auto | SubtitleSource::Both) {
+        args.push("--write-auto-subs".into());
+    }
+
+    let extension = options.format.extension();
+    args.push("--sub-langs".into());
+    args.push(languages.join(","));
+    args.push("--sub-format".into());
+    args.push(format!("{extension}/best"));
+    args.push("--convert-subs".into());
+    args.push(extension.into());
+
+    if options.embed {
+        args.push("--embed-subs".into());
+    }
+
+    Ok(())
+}
+
+struct CompiledFormat {
+    format: String,
+    sort: Option<String>,
+}
+
+fn push_format_args(
+    args: &mut Vec<String>,
+    format: Option<CompiledFormat>,
+    preset: impl FnOnce() -> String,
+) {
+    let CompiledFormat { format, sort } = format.unwrap_or_else(|| CompiledFormat {
+        format: preset(),
+        sort: None,
+    });
+
+    args.push("-f".into());
+    args.push(format);
+    if let Some(sort) = sort {
+        args.push("-S".into());
+        args.push(sort);
+    }
+}
+
+/// Appends `filter` to every stream in every fallback of a `-f` expression.
+fn constrain_streams(format: &str, filter: Option<&str>) -> String {
+    let Some(filter) = filter else {
+        return format.to_string();
+    };
+
+    format
+        .split('/')
+        .map(|alternative| {
+            alternative
+                .split('+')
+                .map(|stream| format!("{stream}{filter}"))
+                .collect::<Vec<_>>()
+                .join("+")
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn compile_format_selection(
+    selection: &FormatSelection,
+    mode: DownloadModeArg,
+) -> Result<CompiledFormat, String> {
+    let has_constraints = selection.max_height.is_some()
+        || selection.video_codec.is_some()
+        || selection.audio_codec.is_some()
+        || selection.max_fps.is_some()
+        || selection.hdr.is_some()
+        || selection.max_filesize_mb.is_some();
+
+    if !selection.format_ids.is_empty() {
+        if has_constraints {
+            return Err("指定格式 ID 时不能同时设置其他筛选条件".into());
+        }
+        return compile_format_ids(&selection.format_ids, mode);
+    }
+
+    let has_video_constraints = selection.max_height.is_some()
+        || selection.video_codec.is_some()
+        || selection.max_fps.is_some()
+        || selection.hdr.is_some();
+    if mode == DownloadModeArg::Audio && has_video_constraints {
+        return Err("音频模式不支持分辨率、帧率、视频编码或 HDR 筛选".into());
+    }
+    if selection.max_height == Some(0) {
+        return Err("最大分辨率必须大于 0".into());
+    }
+    if selection.max_fps == Some(0) {
+        return Err("最大帧率必须大于 0".into());
+    }
+    if selection.max_filesize_mb == Some(0) {
+        return Err("最大文件大小必须大于 0".into());
+    }
+    if selection.hdr == Some(true) && selection.video_codec == Some(VideoCodecPreference::H264) {
+        return Err("H.264 编码不提供 HDR 格式，请改选 AV1 或 VP9".into());
+    }
+
+    // Filters are hard limits; codec choices only steer the sort order so a
+    // missing codec falls back to the next best stream instead of failing.
+    let mut video_filter = String::new();
+    if let Some(height) = selection.max_height {
+        video_filter.push_str(&format!("[height<={height}]"));
+    }
+    if let Some(fps) = selection.max_fps {
+        video_filter.push_str(&format!("[fps<=?{fps}]"));
+    }
+    match selection.hdr {
+        Some(true) => video_filter.push_str("[dynamic_range!=SDR]"),
+        Some(false) => video_filter.push_str("[dynamic_range=?SDR]"),
+        None => {}
+    }
+
+    let size_filter = selection
+        .max_filesize_mb
+        .map(|size| format!("[filesize<=?{size}M]"))
+        .unwrap_or_default();
+
+    let format = match mode {
+        DownloadModeArg::Audio => format!("ba{size_filter}/b{size_filter}"),
+        DownloadModeArg::Video => {
+            format!("bv*{video_filter}{size_filter}+ba{size_filter}/b{video_filter}{size_filter}")
+        }
+    };
+
+    let mut sort = Vec::new();
+    if let Some(codec) = selection.video_codec {
+        sort.push(format!(
+            "vcodec:{}",
+            match codec {
+                VideoCodecPreference::Av1 => "av01",
+                VideoCodecPreference::Vp9 => "vp9",
+                VideoCodecPreference::H264 => "h264",
+            }
+        ));
+    }
+    if let Some(codec) = selection.audio_codec {
+        sort.push(format!(
+            "acodec:{}",
+            match codec {
+                AudioCodecPreference::Opus => "opus",
+                AudioCodecPreference::Aac => "aac",
+                AudioCodecPreference::Mp3 => "mp3",
+            }
+        ));
+    }
+
+    Ok(CompiledFormat {
+        format,
+        sort: (!sort.is_empty()).then(|| sort.join(",")),
+    })
+}
+
+fn compile_format_ids(ids: &[String], mode: DownloadModeArg) -> Result<CompiledFormat, String> {
+    let ids: Vec<&str> = ids.iter().map(|id| id.trim()).collect();
+
+    if let Some(invalid) = ids.iter().find(|id| {
+        id.is_empty()
+            || !id
+                .chars()
+                .all(|ch| ch.is_ascii_alphanumeric() || matches!(ch, '-' | '_' | '.'))
+    }) {
+        return Err(format!("无效的格式 ID: {invalid:?}"));
+    }
+
+    let max_ids = match mode {
+        DownloadModeArg::Audio => 1,
+        DownloadModeArg::Video => 2,
+    };
+    if ids.len() > max_ids {
+        return Err(match mode {
+            DownloadModeArg::Audio => "音频模式只能指定一个格式 ID".into(),
+            DownloadModeArg::Video => "最多指定一个视频格式和一个音频格式".into(),
+        });
+    }
+
+    Ok(CompiledFormat {
+        format: ids.join("+"),
+        sort: None,
+    })
+}
+
+/// Every section is written as its own file, so without a per-section field
+/// in the template later sections would collide with the first one.
+fn number_section_files(template: String) -> String {
+    if template.contains("%(section_") {
+        return template;
+    }
+
+    match template.rfind(".%(ext)") {
+        Some(index) => format!(
+            "{} (%(section_number)s){}",
+            &template[..index],
+            &template[index..]
+        ),
+        None => format!("%(section_number)s - {template}"),
+    }
+}
+
+fn apply_browser_cookies(args: &mut Vec<String>, browser: Option<&str>) {
     if let Some(browser) = browser
         .map(str::trim)
         .filter(|value| !value.is_empty() && *value != "none")
@@ -145,35 +1198,107 @@ pub fn build_yt_dlp_args(input: BuildYtDlpArgsInput<'_>) -> Vec<String> {
         args.push("--cookies-from-browser".into());
         args.push(browser.to_string());
     }
+}
 
-    match mode {
-        DownloadModeArg::Audio => {
-            args.push("-f".into());
-            args.push("bestaudio/best".into());
-            args.push("-x".into());
-            args.push("--audio-format".into());
-            args.push("mp3".into());
-            args.push("--embed-thumbnail".into());
-            args.push("--convert-thumbnails".into());
-            args.push("jpg".into());
-        }
-        DownloadModeArg::Video => {
-            args.push("-f".into());
-            args.push(video_format_for_quality(quality, url));
-            args.push("--merge-output-format".into());
-            args.push("mp4".into());
+fn apply_playlist_options(
+    args: &mut Vec<String>,
+    playlist: &PlaylistOptions,
+) -> Result<(), String> {
+    if let Some(items) = playlist
+        .items
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+    {
+        args.push("--playlist-items".into());
+        args.push(normalize_playlist_items(items)?);
+    }
+
+    match playlist.order {
+        PlaylistOrder::Default => {}
+        PlaylistOrder::Reverse => args.push("--playlist-reverse".into()),
+        PlaylistOrder::Random => args.push("--playlist-random".into()),
+    }
+
+    if let Some(max_count) = playlist.max_count {
+        if max_count == 0 {
+            return Err("播放列表最大下载数量必须大于 0".into());
         }
+        args.push("--max-downloads".into());
+        args.push(max_count.to_string());
+    }
+
+    Ok(())
+}
+
+/// Checks a `--playlist-items` spec: comma separated `[start][:|-][end][:step]`
+/// segments, where negative numbers count from the end of the playlist.
+fn normalize_playlist_items(items: &str) -> Result<String, String> {
+    let segments: Vec<String> = items
+        .split(',')
+        .map(|segment| segment.chars().filter(|c| !c.is_whitespace()).collect())
+        .collect();
+
+    if segments
+        .iter()
+        .all(|segment| is_valid_playlist_item_segment(segment))
+    {
+        Ok(segments.join(","))
+    } else {
+        Err(format!("播放列表条目范围无效: {items}"))
+    }
+}
+
+fn is_valid_playlist_item_segment(segment: &str) -> bool {
+    let (start, rest) = take_signed_number(segment);
+    if rest.is_empty() {
+        return start.is_some();
+    }
+
+    let Some(rest) = rest.strip_prefix([':', '-']) else {
+        return false;
+    };
+    let (_, rest) = take_signed_number(rest);
+    if rest.is_empty() {
+        return true;
     }
 
-    if let Some(path) = ffmpeg_path {
-        args.push("--ffmpeg-location".into());
-        args.push(path.to_string_lossy().to_string());
+    let Some(rest) = rest.strip_prefix(':') else {
+        return false;
+    };
+    let (step, rest) = take_signed_number(rest);
+    step.is_some() && rest.is_empty()
+}
+
+fn take_signed_number(value: &str) -> (Option<&str>, &str) {
+    let unsigned = value.strip_prefix(['+', '-']).unwrap_or(value);
+    let digits = unsigned
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(unsigned.len());
+
+    if digits == 0 {
+        (None, value)
+    } else {
+        let split = value.len() - unsigned.len() + digits;
+        (Some(&value[..split]), &value[split..])
     }
+}
 
-    apply_site_specific_overrides(&mut args, url, runtime_caps);
+fn playlist_subfolder(playlist: &PlaylistOptions) -> Option<String> {
+    let candidate = playlist.subfolder_template.as_deref()?.trim();
+    let candidate = candidate.trim_matches(|c| c == '/' || c == '\\');
+    if candidate.is_empty()
+        || candidate.contains('\n')
+        || candidate.contains('\r')
+        || candidate.contains('\0')
+        || candidate
+            .split(['/', '\\'])
+            .any(|segment| segment.trim() == "..")
+    {
+        return None;
+    }
 
-    args.push(url.to_string());
-    args
+    Some(candidate.to_string())
 }
 
 fn apply_site_specific_overrides(
@@ -262,18 +1387,65 @@ mod tests {
     use std::path::Path;
 
     use super::{
-        build_yt_dlp_args, BuildYtDlpArgsInput, DownloadModeArg, DownloadTuning, VideoQualityArg,
+        build_archive_key_args, build_probe_args, build_yt_dlp_args, ffmpeg_requirements,
+        AudioCodecPreference, AudioFormat, AudioOptions, BuildYtDlpArgsInput, ContainerConversion,
+        DownloadModeArg, DownloadTuning, FormatSelection, PlaylistOptions, PlaylistOrder,
+        SponsorBlockCategory, SponsorBlockOptions, SubtitleFormat, SubtitleOptions, SubtitleSource,
+        VideoCodecPreference, VideoContainer, VideoEmbedOptions, VideoOutputOptions,
+        VideoQualityArg,
+    };
+    use crate::{
+        utils::{ffmpeg_features::FfmpegRequirement, yt_dlp::RuntimeCapabilities},
+        yt_dlp_sections::{DownloadSection, SectionOptions},
     };
-    use crate::utils::yt_dlp::RuntimeCapabilities;
 
     fn runtime_caps() -> RuntimeCapabilities {
         RuntimeCapabilities {
             supports_progress_template: true,
             supports_paths_temp: true,
             supports_add_headers: true,
+            supports_embed_metadata: true,
+            supports_embed_chapters: true,
+            supports_embed_info_json: true,
+            supports_sponsorblock: true,
+            supports_no_quiet: true,
         }
     }
 
+    fn input<'a>(
+        url: &'a str,
+        mode: DownloadModeArg,
+        caps: &'a RuntimeCapabilities,
+    ) -> BuildYtDlpArgsInput<'a> {
+        BuildYtDlpArgsInput {
+            url,
+            mode,
+            browser: None,
+            output_dir: Path::new("/tmp/output"),
+            temp_dir: None,
+            quality: VideoQualityArg::Highest,
+            ffmpeg_path: None,
+            runtime_caps: caps,
+            tuning: DownloadTuning::default(),
+            playlist: None,
+            format: None,
+            audio: None,
+            video_output: None,
+            subtitles: None,
+            embed: None,
+            sponsorblock: None,
+            sections: None,
+            download_archive: None,
+        }
+    }
+
+    fn value_after<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+        args.iter()
+            .position(|arg| arg == flag)
+            .and_then(|index| args.get(index + 1))
+            .map(String::as_str)
+    }
+
     #[test]
     fn builds_audio_args_with_stable_defaults() {
         let caps = runtime_caps();
@@ -289,7 +1461,17 @@ mod tests {
             ffmpeg_path: Some(Path::new("/usr/bin/ffmpeg")),
             runtime_caps: &caps,
             tuning,
-        });
+            playlist: None,
+            format: None,
+            audio: None,
+            video_output: None,
+            subtitles: None,
+            embed: None,
+            sponsorblock: None,
+            sections: None,
+            download_archive: None,
+        })
+        .expect("args should build");
 
         assert!(args.contains(&"--progress-template".to_string()));
         assert!(args.contains(&"--cookies-from-browser".to_string()));
@@ -313,7 +1495,17 @@ mod tests {
             ffmpeg_path: None,
             runtime_caps: &caps,
             tuning: DownloadTuning::default(),
-        });
+            playlist: None,
+            format: None,
+            audio: None,
+            video_output: None,
+            subtitles: None,
+            embed: None,
+            sponsorblock: None,
+            sections: None,
+            download_archive: None,
+        })
+        .expect("args should build");
 
         assert!(args.contains(&"--add-headers".to_string()));
         assert!(!args.contains(&"--referer".to_string()));
@@ -322,11 +1514,7 @@ mod tests {
 
     #[test]
     fn falls_back_to_legacy_headers_when_add_headers_unavailable() {
-        let caps = RuntimeCapabilities {
-            supports_progress_template: false,
-            supports_paths_temp: false,
-            supports_add_headers: false,
-        };
+        let caps = RuntimeCapabilities::default();
 
         let args = build_yt_dlp_args(BuildYtDlpArgsInput {
             url: "https://www.douyin.com/video/123",
@@ -338,12 +1526,37 @@ mod tests {
             ffmpeg_path: None,
             runtime_caps: &caps,
             tuning: DownloadTuning::default(),
-        });
+            playlist: None,
+            format: None,
+            audio: None,
+            video_output: None,
+            subtitles: None,
+            embed: None,
+            sponsorblock: None,
+            sections: None,
+            download_archive: None,
+        })
+        .expect("args should build");
 
         assert!(args.contains(&"--referer".to_string()));
         assert!(args.contains(&"--user-agent".to_string()));
         assert!(!args.contains(&"--add-headers".to_string()));
         assert!(!args.iter().any(|arg| arg.starts_with("temp:")));
+        assert!(!args.contains(&"--print".to_string()));
+    }
+
+    #[test]
+    fn prints_finished_items_without_going_quiet() {
+        let caps = runtime_caps();
+        let args = build_yt_dlp_args(input(
+            "https://example.com/video",
+            DownloadModeArg::Video,
+            &caps,
+        ))
+        .expect("args should build");
+
+        assert!(value_after(&args, "--print").is_some_and(|value| value.starts_with("after_move:")));
+        assert!(args.contains(&"--no-quiet".to_string()));
     }
 
     #[test]
@@ -361,8 +1574,908 @@ mod tests {
             ffmpeg_path: None,
             runtime_caps: &caps,
             tuning,
-        });
+            playlist: None,
+            format: None,
+            audio: None,
+            video_output: None,
+            subtitles: None,
+            embed: None,
+            sponsorblock: None,
+            sections: None,
+            download_archive: None,
+        })
+        .expect("args should build");
 
         assert!(args.contains(&"%(title).150B [%(id)s].%(ext)s".to_string()));
     }
+
+    #[test]
+    fn single_video_mode_keeps_no_playlist() {
+        let caps = runtime_caps();
+        let args = build_yt_dlp_args(input(
+            "https://www.youtube.com/playlist?list=PL123",
+            DownloadModeArg::Video,
+            &caps,
+        ))
+        .expect("args should build");
+
+        assert!(args.contains(&"--no-playlist".to_string()));
+        assert!(!args.contains(&"--yes-playlist".to_string()));
+    }
+
+    #[test]
+    fn builds_playlist_args_with_items_order_and_subfolder() {
+        let caps = runtime_caps();
+        let playlist = PlaylistOptions {
+            items: Some(" 1-5, 8 ,-3: ".into()),
+            order: PlaylistOrder::Reverse,
+            max_count: Some(4),
+            subfolder_template: Some("%(playlist_title)s/".into()),
+        };
+
+        let args = build_yt_dlp_args(BuildYtDlpArgsInput {
+            playlist: Some(&playlist),
+            ..input(
+                "https://www.youtube.com/playlist?list=PL123",
+                DownloadModeArg::Video,
+                &caps,
+            )
+        })
+        .expect("args should build");
+
+        assert!(args.contains(&"--yes-playlist".to_string()));
+        assert!(!args.contains(&"--no-playlist".to_string()));
+        assert_eq!(value_after(&args, "--playlist-items"), Some("1-5,8,-3:"));
+        assert!(args.contains(&"--playlist-reverse".to_string()));
+        assert_eq!(value_after(&args, "--max-downloads"), Some("4"));
+        assert_eq!(
+            value_after(&args, "-o"),
+            Some("%(playlist_title)s/%(title).150B [%(id)s].%(ext)s")
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_playlist_items() {
+        let caps = runtime_caps();
+        for items in ["1,,2", "a-b", "1:2:3:4", "1-2-3", "3:x"] {
+            let playlist = PlaylistOptions {
+                items: Some(items.into()),
+                ..PlaylistOptions::default()
+            };
+
+            let result = build_yt_dlp_args(BuildYtDlpArgsInput {
+                playlist: Some(&playlist),
+                ..input("https://example.com/list", DownloadModeArg::Video, &caps)
+            });
+
+            assert!(result.is_err(), "{items} should be rejected");
+        }
+    }
+
+    #[test]
+    fn ignores_subfolder_templates_that_escape_output_dir() {
+        let caps = runtime_caps();
+        let playlist = PlaylistOptions {
+            subfolder_template: Some("../%(playlist_title)s".into()),
+            ..PlaylistOptions::default()
+        };
+
+        let args = build_yt_dlp_args(BuildYtDlpArgsInput {
+            playlist: Some(&playlist),
+            ..input("https://example.com/list", DownloadModeArg::Video, &caps)
+        })
+        .expect("args should build");
+
+        assert_eq!(
+            value_after(&args, "-o"),
+            Some("%(title).150B [%(id)s].%(ext)s")
+        );
+    }
+
+    #[test]
+    fn builds_probe_args_with_cookies_and_site_headers() {
+        let caps = runtime_caps();
+        let args = build_probe_args("https://www.douyin.com/video/123", Some("chrome"), &caps);
+
+        assert_eq!(args[0], "--dump-single-json");
+        assert!(args.contains(&"--flat-playlist".to_string()));
+        assert_eq!(value_after(&args, "--cookies-from-browser"), Some("chrome"));
+        assert!(args.contains(&"--add-headers".to_string()));
+        assert!(!args.contains(&"-o".to_string()));
+        assert_eq!(
+            args.last().map(String::as_str),
+            Some("https://www.douyin.com/video/123")
+        );
+    }
+
+    #[test]
+    fn passes_download_archive_and_prints_archive_keys() {
+        let caps = runtime_caps();
+        let archive = Path::new("/tmp/output/.yt-dlp-archive.txt");
+        let args = build_yt_dlp_args(BuildYtDlpArgsInput {
+            download_archive: Some(archive),
+            ..input(
+                "https://www.youtube.com/watch?v=demo",
+                DownloadModeArg::Video,
+                &caps,
+            )
+        })
+        .expect("args should build");
+        assert_eq!(
+            value_after(&args, "--download-archive"),
+            Some("/tmp/output/.yt-dlp-archive.txt")
+        );
+
+        let args = build_archive_key_args(
+            "https://www.youtube.com/playlist?list=PL1",
+            None,
+            true,
+            &caps,
+        );
+        assert!(args.contains(&"--simulate".to_string()));
+        assert!(args.contains(&"--yes-playlist".to_string()));
+        assert_eq!(
+            value_after(&args, "--print"),
+            Some("%(extractor_key,ie_key)s %(id)s")
+        );
+        assert!(!args.contains(&"--download-archive".to_string()));
+    }
+
+    #[test]
+    fn compiles_format_constraints_into_filters_and_sort() {
+        let caps = runtime_caps();
+        let selection = FormatSelection {
+            max_height: Some(1080),
+            video_codec: Some(VideoCodecPreference::Vp9),
+            audio_codec: Some(AudioCodecPreference::Opus),
+            max_fps: Some(30),
+            hdr: Some(false),
+            max_filesize_mb: Some(500),
+            ..FormatSelection::default()
+        };
+        let args = build_yt_dlp_args(BuildYtDlpArgsInput {
+            format: Some(&selection),
+            ..input("https://example.com/video", DownloadModeArg::Video, &caps)
+        })
+        .expect("args should build");
+
+        assert_eq!(
+            value_after(&args, "-f"),
+            Some(
+                "bv*[height<=1080][fps<=?30][dynamic_range=?SDR][filesize<=?500M]+ba[filesize<=?500M]\
+                 /b[height<=1080][fps<=?30][dynamic_range=?SDR][filesize<=?500M]"
+            )
+        );
+        assert_eq!(value_after(&args, "-S"), Some("vcodec:vp9,acodec:opus"));
+    }
+
+    #[test]
+    fn exact_format_ids_replace_quality_preset() {
+        let caps = runtime_caps();
+        let selection = FormatSelection {
+            format_ids: vec!["137".into(), " 251 ".into()],
+            ..FormatSelection::default()
+        };
+        let args = build_yt_dlp_args(BuildYtDlpArgsInput {
+            format: Some(&selection),
+            ..input("https://example.com/video", DownloadModeArg::Video, &caps)
+        })
+        .expect("args should build");
+
+        assert_eq!(value_after(&args, "-f"), Some("137+251"));
+        assert!(!args.contains(&"-S".to_string()));
+    }
+
+    #[test]
+    fn empty_format_selection_keeps_quality_preset() {
+        let caps = runtime_caps();
+        let selection = FormatSelection::default();
+        let url = "https://www.bilibili.com/video/BV1xx411c7mD";
+
+        let preset = build_yt_dlp_args(input(url, DownloadModeArg::Video, &caps))
+            .expect("args should build");
+        let args = build_yt_dlp_args(BuildYtDlpArgsInput {
+            format: Some(&selection),
+            ..input(url, DownloadModeArg::Video, &caps)
+        })
+        .expect("args should build");
+
+        assert_eq!(value_after(&args, "-f"), value_after(&preset, "-f"));
+        assert!(!args.contains(&"-S".to_string()));
+
+        let audio = build_yt_dlp_args(BuildYtDlpArgsInput {
+            format: Some(&selection),
+            ..input(url, DownloadModeArg::Audio, &caps)
+        })
+        .expect("args should build");
+        assert_eq!(value_after(&audio, "-f"), Some("bestaudio/best"));
+    }
+
+    #[test]
+    fn audio_mode_accepts_audio_only_constraints() {
+        let caps = runtime_caps();
+        let selection = FormatSelection {
+            audio_codec: Some(AudioCodecPreference::Aac),
+            max_filesize_mb: Some(50),
+            ..FormatSelection::default()
+        };
+        let args = build_yt_dlp_args(BuildYtDlpArgsInput {
+            format: Some(&selection),
+            ..input("https://example.com/audio", DownloadModeArg::Audio, &caps)
+        })
+        .expect("args should build");
+
+        assert_eq!(
+            value_after(&args, "-f"),
+            Some("ba[filesize<=?50M]/b[filesize<=?50M]")
+        );
+        assert_eq!(value_after(&args, "-S"), Some("acodec:aac"));
+    }
+
+    #[test]
+    fn rejects_invalid_format_selections() {
+        let caps = runtime_caps();
+        let cases = [
+            (
+                DownloadModeArg::Video,
+                FormatSelection {
+                    format_ids: vec!["137".into()],
+                    max_height: Some(720),
+                    ..FormatSelection::default()
+                },
+            ),
+            (
+                DownloadModeArg::Video,
+                FormatSelection {
+                    format_ids: vec!["137".into(), "140".into(), "251".into()],
+                    ..FormatSelection::default()
+                },
+            ),
+            (
+                DownloadModeArg::Video,
+                FormatSelection {
+                    format_ids: vec!["137 --exec rm".into()],
+                    ..FormatSelection::default()
+                },
+            ),
+            (
+                DownloadModeArg::Audio,
+                FormatSelection {
+                    format_ids: vec!["140".into(), "251".into()],
+                    ..FormatSelection::default()
+                },
+            ),
+            (
+                DownloadModeArg::Audio,
+                FormatSelection {
+                    max_height: Some(720),
+                    ..FormatSelection::default()
+                },
+            ),
+            (
+                DownloadModeArg::Video,
+                FormatSelection {
+                    max_fps: Some(0),
+                    ..FormatSelection::default()
+                },
+            ),
+            (
+                DownloadModeArg::Video,
+                FormatSelection {
+                    hdr: Some(true),
+                    video_codec: Some(VideoCodecPreference::H264),
+                    ..FormatSelection::default()
+                },
+            ),
+        ];
+
+        for (mode, selection) in cases {
+            let result = build_yt_dlp_args(BuildYtDlpArgsInput {
+                format: Some(&selection),
+                ..input("https://example.com/video", mode, &caps)
+            });
+            assert!(result.is_err(), "{selection:?} should be rejected");
+        }
+    }
+
+    #[test]
+    fn covers_every_audio_format_quality_and_thumbnail_combination() {
+        let caps = runtime_caps();
+        // (format, --audio-format value, accepts quality, embeds by default, can embed)
+        let formats = [
+            (AudioFormat::Mp3, "mp3", true, true, true),
+            (AudioFormat::M4a, "m4a", true, true, true),
+            (AudioFormat::Aac, "aac", true, false, false),
+            (AudioFormat::Opus, "opus", true, true, true),
+            (AudioFormat::Flac, "flac", false, true, true),
+            (AudioFormat::Wav, "wav", false, false, false),
+            (AudioFormat::Original, "best", false, false, true),
+        ];
+        let qualities = [None, Some("0"), Some("192k")];
+        let embeds = [None, Some(true), Some(false)];
+
+        for (format, value, accepts_quality, embeds_by_default, can_embed) in formats {
+            for quality in qualities {
+                for embed_thumbnail in embeds {
+                    let options = AudioOptions {
+                        format,
+                        quality: quality.map(str::to_string),
+                        embed_thumbnail,
+                    };
+                    let result = build_yt_dlp_args(BuildYtDlpArgsInput {
+                        audio: Some(&options),
+                        ..input("https://example.com/audio", DownloadModeArg::Audio, &caps)
+                    });
+
+                    let valid = (quality.is_none() || accepts_quality)
+                        && (embed_thumbnail != Some(true) || can_embed);
+                    let Ok(args) = result else {
+                        assert!(!valid, "{options:?} should build");
+                        continue;
+                    };
+                    assert!(valid, "{options:?} should be rejected");
+
+                    assert_eq!(value_after(&args, "--audio-format"), Some(value));
+                    assert_eq!(
+                        value_after(&args, "--audio-quality"),
+                        quality.map(|quality| if quality == "0" { "0" } else { "192K" })
+                    );
+                    assert_eq!(
+                        args.contains(&"--embed-thumbnail".to_string()),
+                        embed_thumbnail.unwrap_or(embeds_by_default),
+                        "{options:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_out_of_range_audio_quality() {
+        let caps = runtime_caps();
+        for quality in ["11", "-1", "4k", "1000K", "192 kbps"] {
+            let options = AudioOptions {
+                quality: Some(quality.into()),
+                ..AudioOptions::default()
+            };
+            let result = build_yt_dlp_args(BuildYtDlpArgsInput {
+                audio: Some(&options),
+                ..input("https://example.com/audio", DownloadModeArg::Audio, &caps)
+            });
+            assert!(result.is_err(), "{quality} should be rejected");
+        }
+    }
+
+    #[test]
+    fn builds_container_and_conversion_args() {
+        let caps = runtime_caps();
+        let cases = [
+            (VideoContainer::Mkv, ContainerConversion::Merge, None),
+            (
+                VideoContainer::Webm,
+                ContainerConversion::Remux,
+                Some("--remux-video"),
+            ),
+            (
+                VideoContainer::Mov,
+                ContainerConversion::Recode,
+                Some("--recode-video"),
+            ),
+        ];
+
+        for (container, conversion, conversion_flag) in cases {
+            let options = VideoOutputOptions {
+                container,
+                conversion,
+            };
+            let args = build_yt_dlp_args(BuildYtDlpArgsInput {
+                video_output: Some(&options),
+                ..input("https://example.com/video", DownloadModeArg::Video, &caps)
+            })
+            .expect("args should build");
+
+            let extension = container.extension();
+            assert_eq!(value_after(&args, "--merge-output-format"), Some(extension));
+            assert_eq!(
+                args.contains(&"--remux-video".to_string()),
+                conversion_flag == Some("--remux-video")
+            );
+            assert_eq!(
+                args.contains(&"--recode-video".to_string()),
+                conversion_flag == Some("--recode-video")
+            );
+            if let Some(flag) = conversion_flag {
+                assert_eq!(value_after(&args, flag), Some(extension));
+            }
+        }
+    }
+
+    #[test]
+    fn filters_streams_to_codecs_the_container_can_hold() {
+        let caps = runtime_caps();
+        let build = |container, conversion, format: Option<&FormatSelection>| {
+            let options = VideoOutputOptions {
+                container,
+                conversion,
+            };
+            build_yt_dlp_args(BuildYtDlpArgsInput {
+                format,
+                video_output: Some(&options),
+                ..input("https://example.com/video", DownloadModeArg::Video, &caps)
+            })
+            .expect("args should build")
+        };
+        let mp4_audio = "[acodec~=?'^(mp4a|aac|mp3|none)']";
+
+        let args = build(VideoContainer::Mp4, ContainerConversion::Merge, None);
+        assert_eq!(
+            value_after(&args, "-f"),
+            Some(format!("bv*{mp4_audio}+ba{mp4_audio}/b{mp4_audio}").as_str())
+        );
+
+        let ids = FormatSelection {
+            format_ids: vec!["137".into(), "251".into()],
+            ..FormatSelection::default()
+        };
+        let args = build(VideoContainer::Mov, ContainerConversion::Remux, Some(&ids));
+        assert_eq!(value_after(&args, "-f"), Some("137+251"));
+        assert_eq!(value_after(&args, "--remux-video"), Some("mov"));
+
+        for (container, conversion) in [
+            (VideoContainer::Mkv, ContainerConversion::Merge),
+            (VideoContainer::Mp4, ContainerConversion::Recode),
+        ] {
+            let args = build(container, conversion, None);
+            assert_eq!(value_after(&args, "-f"), Some("bv*+ba/b"));
+        }
+    }
+
+    #[test]
+    fn flags_codecs_the_container_cannot_hold() {
+        let caps = runtime_caps();
+        let build = |container, conversion, video_codec, audio_codec| {
+            let selection = FormatSelection {
+                video_codec,
+                audio_codec,
+                ..FormatSelection::default()
+            };
+            let options = VideoOutputOptions {
+                container,
+                conversion,
+            };
+            build_yt_dlp_args(BuildYtDlpArgsInput {
+                format: Some(&selection),
+                video_output: Some(&options),
+                ..input("https://example.com/video", DownloadModeArg::Video, &caps)
+            })
+        };
+
+        let opus = Some(AudioCodecPreference::Opus);
+        let aac = Some(AudioCodecPreference::Aac);
+        let h264 = Some(VideoCodecPreference::H264);
+        let vp9 = Some(VideoCodecPreference::Vp9);
+        let merge = ContainerConversion::Merge;
+
+        assert!(build(VideoContainer::Mp4, merge, None, opus).is_err());
+        assert!(build(VideoContainer::Mp4, ContainerConversion::Remux, None, opus).is_err());
+        assert!(build(VideoContainer::Mp4, ContainerConversion::Recode, None, opus).is_ok());
+        assert!(build(VideoContainer::Mp4, merge, vp9, aac).is_ok());
+        assert!(build(VideoContainer::Webm, merge, h264, None).is_err());
+        assert!(build(VideoContainer::Webm, merge, None, aac).is_err());
+        assert!(build(VideoContainer::Webm, merge, vp9, opus).is_ok());
+        assert!(build(VideoContainer::Mov, merge, vp9, None).is_err());
+        assert!(build(VideoContainer::Mov, merge, h264, aac).is_ok());
+        assert!(build(VideoContainer::Mkv, merge, h264, opus).is_ok());
+    }
+
+    #[test]
+    fn builds_subtitle_args_for_sidecar_and_embed() {
+        let caps = runtime_caps();
+        let subtitles = SubtitleOptions {
+            languages: vec!["en".into(), " zh-Hans ".into(), "-live_chat".into()],
+            source: SubtitleSource::Both,
+            format: SubtitleFormat::Ass,
+            embed: false,
+        };
+        let args = build_yt_dlp_args(BuildYtDlpArgsInput {
+            subtitles: Some(&subtitles),
+            ..input("https://example.com/video", DownloadModeArg::Audio, &caps)
+        })
+        .expect("args should build");
+
+        assert!(args.contains(&"--write-subs".to_string()));
+        assert!(args.contains(&"--write-auto-subs".to_string()));
+        assert_eq!(
+            value_after(&args, "--sub-langs"),
+            Some("en,zh-Hans,-live_chat")
+        );
+        assert_eq!(value_after(&args, "--sub-format"), Some("ass/best"));
+        assert_eq!(value_after(&args, "--convert-subs"), Some("ass"));
+        assert!(!args.contains(&"--embed-subs".to_string()));
+
+        let subtitles = SubtitleOptions {
+            languages: vec!["en.*".into()],
+            source: SubtitleSource::Auto,
+            embed: true,
+            ..SubtitleOptions::default()
+        };
+        let args = build_yt_dlp_args(BuildYtDlpArgsInput {
+            subtitles: Some(&subtitles),
+            ..input("https://example.com/video", DownloadModeArg::Video, &caps)
+        })
+        .expect("args should build");
+
+        assert!(!args.contains(&"--write-subs".to_string()));
+        assert!(args.contains(&"--write-auto-subs".to_string()));
+        assert_eq!(value_after(&args, "--convert-subs"), Some("srt"));
+        assert!(args.contains(&"--embed-subs".to_string()));
+    }
+
+    #[test]
+    fn rejects_invalid_subtitle_options() {
+        let caps = runtime_caps();
+        let webm = VideoOutputOptions {
+            container: VideoContainer::Webm,
+            ..VideoOutputOptions::default()
+        };
+        let cases = [
+            (DownloadModeArg::Video, None, vec![" "], false),
+            (DownloadModeArg::Video, None, vec!["en,ja"], false),
+            (DownloadModeArg::Audio, None, vec!["en"], true),
+            (DownloadModeArg::Video, Some(&webm), vec!["en"], true),
+        ];
+
+        for (mode, video_output, languages, embed) in cases {
+            let subtitles = SubtitleOptions {
+                languages: languages.into_iter().map(str::to_string).collect(),
+                embed,
+                ..SubtitleOptions::default()
+            };
+            let result = build_yt_dlp_args(BuildYtDlpArgsInput {
+                video_output,
+                subtitles: Some(&subtitles),
+                ..input("https://example.com/video", mode, &caps)
+            });
+            assert!(result.is_err(), "{subtitles:?} should be rejected");
+        }
+    }
+
+    #[test]
+    fn builds_video_embed_and_sidecar_args() {
+        let caps = runtime_caps();
+        let embed = VideoEmbedOptions {
+            metadata: true,
+            chapters: true,
+            thumbnail: true,
+            info_json: true,
+            write_description: true,
+            write_info_json: true,
+        };
+        let mkv = VideoOutputOptions {
+            container: VideoContainer::Mkv,
+            ..VideoOutputOptions::default()
+        };
+        let args = build_yt_dlp_args(BuildYtDlpArgsInput {
+            ffmpeg_path: Some(Path::new("/usr/bin/ffmpeg")),
+            video_output: Some(&mkv),
+            embed: Some(&embed),
+            ..input("https://example.com/video", DownloadModeArg::Video, &caps)
+        })
+        .expect("args should build");
+
+        for flag in [
+            "--embed-metadata",
+            "--embed-chapters",
+            "--embed-thumbnail",
+            "--embed-info-json",
+            "--write-description",
+            "--write-info-json",
+        ] {
+            assert!(args.contains(&flag.to_string()), "missing {flag}");
+        }
+        assert_eq!(value_after(&args, "--convert-thumbnails"), Some("jpg"));
+
+        let sidecars_only = VideoEmbedOptions {
+            write_description: true,
+            ..VideoEmbedOptions::default()
+        };
+        let args = build_yt_dlp_args(BuildYtDlpArgsInput {
+            embed: Some(&sidecars_only),
+            ..input("https://example.com/video", DownloadModeArg::Video, &caps)
+        })
+        .expect("sidecars should not need ffmpeg");
+        assert!(args.contains(&"--write-description".to_string()));
+        assert!(!args.contains(&"--embed-thumbnail".to_string()));
+    }
+
+    #[test]
+    fn gates_video_embeds_on_ffmpeg_capabilities_and_container() {
+        let caps = runtime_caps();
+        let ffmpeg = Some(Path::new("/usr/bin/ffmpeg"));
+        let chapters = VideoEmbedOptions {
+            chapters: true,
+            ..VideoEmbedOptions::default()
+        };
+
+        let missing_ffmpeg = build_yt_dlp_args(BuildYtDlpArgsInput {
+            embed: Some(&chapters),
+            ..input("https://example.com/video", DownloadModeArg::Video, &caps)
+        });
+        assert!(missing_ffmpeg.is_err());
+
+        let old_caps = RuntimeCapabilities {
+            supports_embed_chapters: false,
+            ..runtime_caps()
+        };
+        let unsupported = build_yt_dlp_args(BuildYtDlpArgsInput {
+            ffmpeg_path: ffmpeg,
+            embed: Some(&chapters),
+            ..input(
+                "https://example.com/video",
+                DownloadModeArg::Video,
+                &old_caps,
+            )
+        });
+        assert!(unsupported.is_err());
+
+        let info_json = VideoEmbedOptions {
+            info_json: true,
+            ..VideoEmbedOptions::default()
+        };
+        let default_mp4 = build_yt_dlp_args(BuildYtDlpArgsInput {
+            ffmpeg_path: ffmpeg,
+            embed: Some(&info_json),
+            ..input("https://example.com/video", DownloadModeArg::Video, &caps)
+        });
+        assert!(default_mp4.is_err());
+
+        let thumbnail = VideoEmbedOptions {
+            thumbnail: true,
+            ..VideoEmbedOptions::default()
+        };
+        let webm = VideoOutputOptions {
+            container: VideoContainer::Webm,
+            ..VideoOutputOptions::default()
+        };
+        let webm_thumbnail = build_yt_dlp_args(BuildYtDlpArgsInput {
+            ffmpeg_path: ffmpeg,
+            video_output: Some(&webm),
+            embed: Some(&thumbnail),
+            ..input("https://example.com/video", DownloadModeArg::Video, &caps)
+        });
+        assert!(webm_thumbnail.is_err());
+
+        let audio_mode = build_yt_dlp_args(BuildYtDlpArgsInput {
+            ffmpeg_path: ffmpeg,
+            embed: Some(&thumbnail),
+            ..input("https://example.com/video", DownloadModeArg::Audio, &caps)
+        });
+        assert!(audio_mode.is_err());
+    }
+
+    #[test]
+    fn builds_sponsorblock_args_with_custom_api_server() {
+        let caps = runtime_caps();
+        let options = SponsorBlockOptions {
+            mark: vec![
+                SponsorBlockCategory::Intro,
+                SponsorBlockCategory::PoiHighlight,
+            ],
+            remove: vec![
+                SponsorBlockCategory::Sponsor,
+                SponsorBlockCategory::MusicOfftopic,
+            ],
+            api_url: Some("https://sb.example.com/".into()),
+        };
+        let args = build_yt_dlp_args(BuildYtDlpArgsInput {
+            ffmpeg_path: Some(Path::new("/usr/bin/ffmpeg")),
+            sponsorblock: Some(&options),
+            ..input("https://example.com/video", DownloadModeArg::Video, &caps)
+        })
+        .expect("args should build");
+
+        assert_eq!(
+            value_after(&args, "--sponsorblock-mark"),
+            Some("intro,poi_highlight")
+        );
+        assert_eq!(
+            value_after(&args, "--sponsorblock-remove"),
+            Some("sponsor,music_offtopic")
+        );
+        assert_eq!(
+            value_after(&args, "--sponsorblock-api"),
+            Some("https://sb.example.com")
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_sponsorblock_options() {
+        let caps = runtime_caps();
+        let ffmpeg = Some(Path::new("/usr/bin/ffmpeg"));
+        let build = |options: &SponsorBlockOptions, caps, ffmpeg_path| {
+            build_yt_dlp_args(BuildYtDlpArgsInput {
+                ffmpeg_path,
+                sponsorblock: Some(options),
+                ..input("https://example.com/video", DownloadModeArg::Video, caps)
+            })
+        };
+        let remove_sponsor = SponsorBlockOptions {
+            remove: vec![SponsorBlockCategory::Sponsor],
+            ..SponsorBlockOptions::default()
+        };
+
+        let no_sponsorblock = RuntimeCapabilities {
+            supports_sponsorblock: false,
+            ..runtime_caps()
+        };
+        assert!(build(&remove_sponsor, &no_sponsorblock, ffmpeg).is_err());
+        assert!(build(&remove_sponsor, &caps, None).is_err());
+
+        let remove_highlight = SponsorBlockOptions {
+            remove: vec![SponsorBlockCategory::PoiHighlight],
+            ..SponsorBlockOptions::default()
+        };
+        assert!(build(&remove_highlight, &caps, ffmpeg).is_err());
+
+        let overlap = SponsorBlockOptions {
+            mark: vec![SponsorBlockCategory::Sponsor],
+            ..remove_sponsor.clone()
+        };
+        assert!(build(&overlap, &caps, ffmpeg).is_err());
+
+        let bad_api = SponsorBlockOptions {
+            api_url: Some("sponsor.example.com".into()),
+            ..remove_sponsor.clone()
+        };
+        assert!(build(&bad_api, &caps, ffmpeg).is_err());
+
+        let empty = SponsorBlockOptions::default();
+        let args = build(&empty, &no_sponsorblock, None).expect("empty options are a no-op");
+        assert!(!args.iter().any(|arg| arg.starts_with("--sponsorblock")));
+    }
+
+    #[test]
+    fn builds_download_sections_and_numbers_section_files() {
+        let caps = runtime_caps();
+        let sections = SectionOptions {
+            sections: vec![
+                DownloadSection::Time {
+                    start: Some("1:00:00".into()),
+                    end: Some("1:00:30".into()),
+                },
+                DownloadSection::Chapter {
+                    pattern: "Outro".into(),
+                },
+            ],
+            force_keyframes_at_cuts: true,
+        };
+        let args = build_yt_dlp_args(BuildYtDlpArgsInput {
+            ffmpeg_path: Some(Path::new("/usr/bin/ffmpeg")),
+            sections: Some(&sections),
+            ..input("https://example.com/stream", DownloadModeArg::Video, &caps)
+        })
+        .expect("args should build");
+
+        let specs: Vec<&str> = args
+            .iter()
+            .enumerate()
+            .filter(|(_, arg)| *arg == "--download-sections")
+            .map(|(index, _)| args[index + 1].as_str())
+            .collect();
+        assert_eq!(specs, ["*3600-3630", "Outro"]);
+        assert!(args.contains(&"--force-keyframes-at-cuts".to_string()));
+        assert_eq!(
+            value_after(&args, "-o"),
+            Some("%(title).150B [%(id)s] (%(section_number)s).%(ext)s")
+        );
+    }
+
+    #[test]
+    fn numbers_files_for_a_single_chapter_pattern() {
+        let caps = runtime_caps();
+        let build = |section| {
+            let sections = SectionOptions {
+                sections: vec![section],
+                force_keyframes_at_cuts: false,
+            };
+            build_yt_dlp_args(BuildYtDlpArgsInput {
+                ffmpeg_path: Some(Path::new("/usr/bin/ffmpeg")),
+                sections: Some(&sections),
+                ..input("https://example.com/stream", DownloadModeArg::Video, &caps)
+            })
+            .expect("args should build")
+        };
+
+        let args = build(DownloadSection::Chapter {
+            pattern: "Part".into(),
+        });
+        assert_eq!(
+            value_after(&args, "-o"),
+            Some("%(title).150B [%(id)s] (%(section_number)s).%(ext)s")
+        );
+
+        let args = build(DownloadSection::Time {
+            start: Some("10".into()),
+            end: Some("20".into()),
+        });
+        assert_eq!(
+            value_after(&args, "-o"),
+            Some("%(title).150B [%(id)s].%(ext)s")
+        );
+    }
+
+    #[test]
+    fn rejects_sections_before_spawning_yt_dlp() {
+        let caps = runtime_caps();
+        let backwards = SectionOptions {
+            sections: vec![DownloadSection::Time {
+                start: Some("90s".into()),
+                end: Some("1:00".into()),
+            }],
+            force_keyframes_at_cuts: false,
+        };
+
+        assert!(build_yt_dlp_args(BuildYtDlpArgsInput {
+            ffmpeg_path: Some(Path::new("/usr/bin/ffmpeg")),
+            sections: Some(&backwards),
+            ..input("https://example.com/stream", DownloadModeArg::Video, &caps)
+        })
+        .is_err());
+
+        let valid = SectionOptions {
+            sections: vec![DownloadSection::Time {
+                start: None,
+                end: Some("30".into()),
+            }],
+            force_keyframes_at_cuts: false,
+        };
+        let without_ffmpeg = build_yt_dlp_args(BuildYtDlpArgsInput {
+            sections: Some(&valid),
+            ..input("https://example.com/stream", DownloadModeArg::Video, &caps)
+        });
+        assert!(without_ffmpeg.is_err());
+    }
+
+    #[test]
+    fn lists_ffmpeg_requirements_of_the_requested_output() {
+        let audio = |format| AudioOptions {
+            format,
+            ..AudioOptions::default()
+        };
+
+        assert_eq!(
+            ffmpeg_requirements(DownloadModeArg::Audio, None, None),
+            [
+                FfmpegRequirement::Encoder {
+                    any_of: &["libmp3lame"],
+                    output: "MP3 音频",
+                },
+                FfmpegRequirement::Muxer {
+                    name: "mp3",
+                    output: "MP3 音频",
+                },
+            ]
+        );
+        assert!(ffmpeg_requirements(
+            DownloadModeArg::Audio,
+            Some(&audio(AudioFormat::Original)),
+            None
+        )
+        .is_empty());
+        assert_eq!(
+            ffmpeg_requirements(
+                DownloadModeArg::Video,
+                Some(&audio(AudioFormat::Opus)),
+                Some(&VideoOutputOptions {
+                    container: VideoContainer::Mkv,
+                    conversion: ContainerConversion::Recode,
+                }),
+            ),
+            [FfmpegRequirement::Muxer {
+                name: "matroska",
+                output: "MKV 视频",
+            }]
+        );
+        assert!(ffmpeg_requirements(DownloadModeArg::Video, None, None).is_empty());
+    }
 }
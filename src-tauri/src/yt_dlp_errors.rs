@@ -0,0 +1,355 @@
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ErrorKind {
+    GeoBlocked,
+    LoginRequired,
+    PrivateVideo,
+    Unavailable,
+    AgeRestricted,
+    UnsupportedUrl,
+    HttpForbidden,
+    RateLimited,
+    FfmpegMissing,
+    DiskFull,
+    ExtractorBroken,
+    Unknown,
+}
+
+impl ErrorKind {
+    pub fn hint(self) -> &'static str {
+        match self {
+            ErrorKind::GeoBlocked => "该内容在当前地区不可用，可尝试使用代理或 VPN 后重试",
+            ErrorKind::LoginRequired => {
+                "该内容需要登录，请选择已登录该网站的浏览器以读取 Cookies 后重试"
+            }
+            ErrorKind::PrivateVideo => {
+                "这是私享内容，只有获得授权的账号才能访问，请使用对应账号的浏览器 Cookies"
+            }
+            ErrorKind::Unavailable => "该内容已被删除、下架或无法访问，请确认链接是否仍然有效",
+            ErrorKind::AgeRestricted => {
+                "该内容有年龄限制，请选择已登录且通过年龄验证的浏览器 Cookies"
+            }
+            ErrorKind::UnsupportedUrl => "yt-dlp 不支持该链接，请确认链接是否为视频页面地址",
+            ErrorKind::HttpForbidden => {
+                "服务器拒绝访问 (HTTP 403)，可尝试更新 yt-dlp 或使用浏览器 Cookies 后重试"
+            }
+            ErrorKind::RateLimited => "请求过于频繁 (HTTP 429)，请稍后再试或使用浏览器 Cookies",
+            ErrorKind::FfmpegMissing => "当前操作需要 FFmpeg，请先安装 FFmpeg",
+            ErrorKind::DiskFull => "磁盘空间不足，请清理空间或更换下载目录",
+            ErrorKind::ExtractorBroken => {
+                "网站可能已更新，当前 yt-dlp 无法解析，请更新 yt-dlp 后重试"
+            }
+            ErrorKind::Unknown => "下载失败，请查看日志了解详情",
+        }
+    }
+
+    /// Whether updating yt-dlp and retrying once may fix the failure. Besides
+    /// broken extractors this includes "video unavailable", which an outdated
+    /// extractor also reports when it can no longer read the page.
+    pub fn worth_updating_for(self) -> bool {
+        matches!(self, ErrorKind::ExtractorBroken | ErrorKind::Unavailable)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClassifiedError {
+    pub kind: ErrorKind,
+    /// The stderr line the classification is based on.
+    pub message: String,
+    pub hint: String,
+}
+
+/// Checked in order, so specific causes win over the generic "please update"
+/// footer yt-dlp appends to many unrelated errors.
+const PATTERNS: &[(ErrorKind, &[&str])] = &[
+    (
+        ErrorKind::DiskFull,
+        &[
+            "no space left on device",
+            "errno 28",
+            "not enough space on the disk",
+            "winerror 112",
+        ],
+    ),
+    (
+        ErrorKind::FfmpegMissing,
+        &[
+            "ffmpeg not found",
+            "ffmpeg is not installed",
+            "ffprobe and ffmpeg not found",
+            "ffmpeg could not be found",
+        ],
+    ),
+    (
+        ErrorKind::GeoBlocked,
+        &[
+            "available in your country",
+            "not available from your location",
+            "geo restriction",
+            "geo-restricted",
+            "geo restricted",
+        ],
+    ),
+    (
+        ErrorKind::AgeRestricted,
+        &[
+            "sign in to confirm your age",
+            "age-restricted",
+            "age restricted",
+            "inappropriate for some users",
+        ],
+    ),
+    (
+        ErrorKind::PrivateVideo,
+        &["private video", "video is private"],
+    ),
+    (
+        ErrorKind::Unavailable,
+        &[
+            "this video is unavailable",
+            "video unavailable",
+            "this video has been removed",
+            "account associated with this video has been terminated",
+        ],
+    ),
+    (
+        ErrorKind::LoginRequired,
+        &[
+            "not a bot",
+            "login required",
+            "login is required",
+            "requires authentication",
+            "only available for registered users",
+            "members-only",
+            "join this channel",
+            "use --cookies",
+        ],
+    ),
+    (ErrorKind::UnsupportedUrl, &["unsupported url"]),
+    (
+        ErrorKind::RateLimited,
+        &["http error 429", "too many requests"],
+    ),
+    (
+        ErrorKind::HttpForbidden,
+        &["http error 403", "403: forbidden"],
+    ),
+    (
+        ErrorKind::ExtractorBroken,
+        &[
+            "unable to extract",
+            "unable to download api page",
+            "nsig extraction failed",
+            "signature extraction failed",
+            "please report this issue",
+            "confirm you are on the latest version",
+        ],
+    ),
+];
+
+/// Maps yt-dlp's stderr to a known failure cause; `None` when it contains no
+/// error at all, `Unknown` when it has an `ERROR:` line nothing matched.
+///
+/// `ERROR:` lines are matched first so a non-fatal warning, e.g. a 429 on a
+/// subtitle fetch, cannot outrank the reason yt-dlp actually gave up.
+pub fn classify_stderr(stderr: &str) -> Option<ClassifiedError> {
+    let lines: Vec<&str> = stderr
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect();
+    let errors: Vec<&str> = lines
+        .iter()
+        .filter(|line| line.starts_with("ERROR:"))
+        .copied()
+        .collect();
+
+    let matched = match_kind(&errors).or_else(|| match_kind(&lines));
+    if let Some((kind, line)) = matched {
+        return Some(classified(kind, error_line(&lines).unwrap_or(line)));
+    }
+
+    error_line(&lines).map(|line| classified(ErrorKind::Unknown, line))
+}
+
+fn match_kind<'a>(lines: &[&'a str]) -> Option<(ErrorKind, &'a str)> {
+    let lowercase: Vec<String> = lines.iter().map(|line| line.to_lowercase()).collect();
+
+    PATTERNS.iter().find_map(|(kind, needles)| {
+        lowercase
+            .iter()
+            .position(|line| needles.iter().any(|needle| line.contains(needle)))
+            .map(|index| (*kind, lines[index]))
+    })
+}
+
+/// The last `ERROR:` line is yt-dlp's own summary of why it gave up.
+fn error_line<'a>(lines: &[&'a str]) -> Option<&'a str> {
+    lines
+        .iter()
+        .rev()
+        .find(|line| line.starts_with("ERROR:"))
+        .copied()
+}
+
+fn classified(kind: ErrorKind, message: &str) -> ClassifiedError {
+    ClassifiedError {
+        kind,
+        message: message.to_string(),
+        hint: kind.hint().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{classify_stderr, ErrorKind};
+
+    fn kind_of(stderr: &str) -> Option<ErrorKind> {
+        classify_stderr(stderr).map(|error| error.kind)
+    }
+
+    #[test]
+    fn classifies_captured_stderr_samples() {
+        let cases = [
+            (
+                "ERROR: [youtube] abc123: The uploader has not made this video available in your country\nThis video may be available to you on a different site",
+                ErrorKind::GeoBlocked,
+            ),
+            (
+                "ERROR: [BiliBili] 12345: This video is not available from your location due to geo restriction",
+                ErrorKind::GeoBlocked,
+            ),
+            (
+                "ERROR: [youtube] dQw4w9WgXcQ: Sign in to confirm you’re not a bot. Use --cookies-from-browser or --cookies for the authentication.",
+                ErrorKind::LoginRequired,
+            ),
+            (
+                "ERROR: [instagram] Cx1: Requested content is not available, rate-limit reached or login required. Use --cookies",
+                ErrorKind::LoginRequired,
+            ),
+            (
+                "ERROR: [youtube] abc123: Private video. Sign in if you've been granted access to this video",
+                ErrorKind::PrivateVideo,
+            ),
+            (
+                "ERROR: [youtube] abc123: Sign in to confirm your age. This video may be inappropriate for some users.",
+                ErrorKind::AgeRestricted,
+            ),
+            (
+                "ERROR: [youtube] dQw4w9WgXcQ: This video is unavailable",
+                ErrorKind::Unavailable,
+            ),
+            (
+                "ERROR: [youtube] abc123: This video is no longer available because the YouTube account associated with this video has been terminated.",
+                ErrorKind::Unavailable,
+            ),
+            (
+                "ERROR: Unsupported URL: https://example.com/not-a-video",
+                ErrorKind::UnsupportedUrl,
+            ),
+            (
+                "ERROR: unable to download video data: HTTP Error 403: Forbidden",
+                ErrorKind::HttpForbidden,
+            ),
+            (
+                "WARNING: [youtube] HTTP Error 429: Too Many Requests. Retrying (1/3)...\nERROR: [youtube] abc123: Unable to download API page: HTTP Error 429: Too Many Requests",
+                ErrorKind::RateLimited,
+            ),
+            (
+                "ERROR: You have requested merging of multiple formats but ffmpeg is not installed. Aborting due to --abort-on-error",
+                ErrorKind::FfmpegMissing,
+            ),
+            (
+                "ERROR: Postprocessing: ffprobe and ffmpeg not found. Please install or provide the path using --ffmpeg-location",
+                ErrorKind::FfmpegMissing,
+            ),
+            (
+                "ERROR: unable to write data: [Errno 28] No space left on device",
+                ErrorKind::DiskFull,
+            ),
+            (
+                "ERROR: unable to write data: [WinError 112] There is not enough space on the disk",
+                ErrorKind::DiskFull,
+            ),
+            (
+                "WARNING: [youtube] nsig extraction failed: You may experience throttling for some formats\nERROR: [youtube] abc123: Unable to extract uploader id; please report this issue on  https://github.com/yt-dlp/yt-dlp/issues?q= , filling out the appropriate issue template. Confirm you are on the latest version using  yt-dlp -U",
+                ErrorKind::ExtractorBroken,
+            ),
+        ];
+
+        for (stderr, expected) in cases {
+            assert_eq!(kind_of(stderr), Some(expected), "{stderr}");
+        }
+    }
+
+    #[test]
+    fn reports_the_last_error_line_with_a_hint() {
+        let stderr = "WARNING: [youtube] HTTP Error 403: Forbidden. Retrying fragment 1 (1/10)...\nERROR: fragment 1 not found, unable to continue\n";
+        let error = classify_stderr(stderr).expect("should classify");
+
+        assert_eq!(error.kind, ErrorKind::HttpForbidden);
+        assert_eq!(
+            error.message,
+            "ERROR: fragment 1 not found, unable to continue"
+        );
+        assert!(!error.hint.is_empty());
+    }
+
+    #[test]
+    fn error_lines_outrank_matching_warnings() {
+        let cases = [
+            (
+                "WARNING: [youtube] abc123: Unable to download subtitles for \"en\": HTTP Error 429: Too Many Requests\nERROR: [youtube] abc123: Unable to extract initial player response; please report this issue on  https://github.com/yt-dlp/yt-dlp/issues?q= , filling out the appropriate issue template.",
+                ErrorKind::ExtractorBroken,
+            ),
+            (
+                "WARNING: [youtube] Skipping player responses from android clients. Use --cookies-from-browser or --cookies for the authentication\nERROR: unable to download video data: HTTP Error 403: Forbidden",
+                ErrorKind::HttpForbidden,
+            ),
+        ];
+
+        for (stderr, expected) in cases {
+            let error = classify_stderr(stderr).expect("should classify");
+            assert_eq!(error.kind, expected, "{stderr}");
+            assert!(error.message.starts_with("ERROR:"));
+        }
+    }
+
+    #[test]
+    fn retries_after_an_update_only_for_fixable_failures() {
+        let cases = [
+            ("ERROR: [youtube] dQw4w9WgXcQ: This video is unavailable", true),
+            (
+                "ERROR: [youtube] abc123: Unable to extract uploader id; please report this issue on  https://github.com/yt-dlp/yt-dlp/issues?q= , filling out the appropriate issue template.",
+                true,
+            ),
+            (
+                "ERROR: [youtube] abc123: Private video. Sign in if you've been granted access to this video",
+                false,
+            ),
+            (
+                "ERROR: unable to write data: [Errno 28] No space left on device",
+                false,
+            ),
+            ("ERROR: something nobody has seen before", false),
+        ];
+
+        for (stderr, expected) in cases {
+            let kind = kind_of(stderr).expect("should classify");
+            assert_eq!(kind.worth_updating_for(), expected, "{stderr}");
+        }
+    }
+
+    #[test]
+    fn falls_back_to_unknown_or_nothing() {
+        assert_eq!(
+            kind_of("ERROR: something nobody has seen before"),
+            Some(ErrorKind::Unknown)
+        );
+        assert_eq!(kind_of("[download] 100% of 3.2MiB\n"), None);
+        assert_eq!(kind_of(""), None);
+    }
+}
@@ -0,0 +1,432 @@
+use serde::{Deserialize, Deserializer, Serialize};
+use std::{collections::BTreeMap, path::Path, process::Stdio};
+use tokio::process::Command;
+
+use crate::{utils::yt_dlp::RuntimeCapabilities, yt_dlp_args::build_probe_args};
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MediaInfo {
+    pub id: String,
+    pub title: String,
+    pub extractor: Option<String>,
+    pub webpage_url: Option<String>,
+    pub uploader: Option<String>,
+    pub duration: Option<f64>,
+    pub is_playlist: bool,
+    pub thumbnails: Vec<Thumbnail>,
+    pub chapters: Vec<Chapter>,
+    pub subtitles: Vec<SubtitleTrack>,
+    pub automatic_captions: Vec<SubtitleTrack>,
+    pub formats: Vec<FormatInfo>,
+    pub entries: Vec<PlaylistEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Thumbnail {
+    pub url: String,
+    pub id: Option<String>,
+    pub width: Option<u64>,
+    pub height: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Chapter {
+    pub title: String,
+    pub start_time: f64,
+    pub end_time: f64,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SubtitleTrack {
+    pub language: String,
+    pub name: Option<String>,
+    pub formats: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AvailableSubtitles {
+    pub subtitles: Vec<SubtitleTrack>,
+    pub automatic_captions: Vec<SubtitleTrack>,
+}
+
+impl From<MediaInfo> for AvailableSubtitles {
+    fn from(info: MediaInfo) -> Self {
+        Self {
+            subtitles: info.subtitles,
+            automatic_captions: info.automatic_captions,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FormatInfo {
+    pub format_id: String,
+    pub ext: Option<String>,
+    pub note: Option<String>,
+    pub protocol: Option<String>,
+    pub video_codec: Option<String>,
+    pub audio_codec: Option<String>,
+    pub width: Option<u64>,
+    pub height: Option<u64>,
+    pub resolution: Option<String>,
+    pub fps: Option<f64>,
+    pub dynamic_range: Option<String>,
+    pub total_bitrate: Option<f64>,
+    pub video_bitrate: Option<f64>,
+    pub audio_bitrate: Option<f64>,
+    pub filesize: Option<u64>,
+    pub filesize_approx: bool,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaylistEntry {
+    pub id: Option<String>,
+    pub title: Option<String>,
+    pub url: Option<String>,
+    pub duration: Option<f64>,
+}
+
+pub async fn probe(
+    binary_path: &Path,
+    url: &str,
+    browser: Option<&str>,
+    runtime_caps: &RuntimeCapabilities,
+) -> Result<MediaInfo, String> {
+    let stdout = run_probe(binary_path, build_probe_args(url, browser, runtime_caps)).await?;
+    parse_media_info(&stdout)
+}
+
+/// Runs a simulate-only yt-dlp invocation and returns its stdout, turning a
+/// failure into the last line yt-dlp printed to stderr.
+pub async fn run_probe(binary_path: &Path, args: Vec<String>) -> Result<String, String> {
+    let output = Command::new(binary_path)
+        .args(args)
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output()
+        .await
+        .map_err(|err| format!("执行 yt-dlp 解析失败: {err}"))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let message = stderr
+            .lines()
+            .rev()
+            .map(str::trim)
+            .find(|line| !line.is_empty())
+            .unwrap_or("yt-dlp 返回失败状态");
+        return Err(format!("解析链接失败: {message}"));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+pub fn parse_media_info(json: &str) -> Result<MediaInfo, String> {
+    let raw: RawInfo =
+        serde_json::from_str(json).map_err(|err| format!("解析 yt-dlp 输出失败: {err}"))?;
+    Ok(raw.into_media_info())
+}
+
+#[derive(Deserialize)]
+struct RawInfo {
+    #[serde(rename = "_type")]
+    kind: Option<String>,
+    id: Option<String>,
+    title: Option<String>,
+    extractor_key: Option<String>,
+    extractor: Option<String>,
+    webpage_url: Option<String>,
+    uploader: Option<String>,
+    channel: Option<String>,
+    #[serde(default, deserialize_with = "lenient_f64")]
+    duration: Option<f64>,
+    #[serde(default)]
+    thumbnails: Vec<RawThumbnail>,
+    #[serde(default)]
+    chapters: Option<Vec<RawChapter>>,
+    #[serde(default)]
+    subtitles: Option<BTreeMap<String, Vec<RawSubtitle>>>,
+    #[serde(default)]
+    automatic_captions: Option<BTreeMap<String, Vec<RawSubtitle>>>,
+    #[serde(default)]
+    formats: Option<Vec<RawFormat>>,
+    #[serde(default)]
+    entries: Option<Vec<RawEntry>>,
+}
+
+#[derive(Deserialize)]
+struct RawThumbnail {
+    url: String,
+    id: Option<String>,
+    #[serde(default, deserialize_with = "lenient_u64")]
+    width: Option<u64>,
+    #[serde(default, deserialize_with = "lenient_u64")]
+    height: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct RawChapter {
+    title: Option<String>,
+    #[serde(default, deserialize_with = "lenient_f64")]
+    start_time: Option<f64>,
+    #[serde(default, deserialize_with = "lenient_f64")]
+    end_time: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct RawSubtitle {
+    ext: Option<String>,
+    name: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RawFormat {
+    format_id: String,
+    ext: Option<String>,
+    format_note: Option<String>,
+    protocol: Option<String>,
+    vcodec: Option<String>,
+    acodec: Option<String>,
+    #[serde(default, deserialize_with = "lenient_u64")]
+    width: Option<u64>,
+    #[serde(default, deserialize_with = "lenient_u64")]
+    height: Option<u64>,
+    resolution: Option<String>,
+    #[serde(default, deserialize_with = "lenient_f64")]
+    fps: Option<f64>,
+    dynamic_range: Option<String>,
+    #[serde(default, deserialize_with = "lenient_f64")]
+    tbr: Option<f64>,
+    #[serde(default, deserialize_with = "lenient_f64")]
+    vbr: Option<f64>,
+    #[serde(default, deserialize_with = "lenient_f64")]
+    abr: Option<f64>,
+    #[serde(default, deserialize_with = "lenient_u64")]
+    filesize: Option<u64>,
+    #[serde(default, deserialize_with = "lenient_u64")]
+    filesize_approx: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct RawEntry {
+    id: Option<String>,
+    title: Option<String>,
+    url: Option<String>,
+    #[serde(default, deserialize_with = "lenient_f64")]
+    duration: Option<f64>,
+}
+
+impl RawInfo {
+    fn into_media_info(self) -> MediaInfo {
+        let is_playlist = matches!(self.kind.as_deref(), Some("playlist" | "multi_video"));
+
+        MediaInfo {
+            id: self.id.unwrap_or_default(),
+            title: self.title.unwrap_or_default(),
+            extractor: self.extractor_key.or(self.extractor),
+            webpage_url: self.webpage_url,
+            uploader: self.uploader.or(self.channel),
+            duration: self.duration,
+            is_playlist,
+            thumbnails: self
+                .thumbnails
+                .into_iter()
+                .map(|thumbnail| Thumbnail {
+                    url: thumbnail.url,
+                    id: thumbnail.id,
+                    width: thumbnail.width,
+                    height: thumbnail.height,
+                })
+                .collect(),
+            chapters: self
+                .chapters
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|chapter| {
+                    Some(Chapter {
+                        title: chapter.title.unwrap_or_default(),
+                        start_time: chapter.start_time?,
+                        end_time: chapter.end_time?,
+                    })
+                })
+                .collect(),
+            subtitles: subtitle_tracks(self.subtitles),
+            automatic_captions: subtitle_tracks(self.automatic_captions),
+            formats: self
+                .formats
+                .unwrap_or_default()
+                .into_iter()
+                .map(RawFormat::into_format_info)
+                .collect(),
+            entries: self
+                .entries
+                .unwrap_or_default()
+                .into_iter()
+                .map(|entry| PlaylistEntry {
+                    id: entry.id,
+                    title: entry.title,
+                    url: entry.url,
+                    duration: entry.duration,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl RawFormat {
+    fn into_format_info(self) -> FormatInfo {
+        FormatInfo {
+            format_id: self.format_id,
+            ext: self.ext,
+            note: self.format_note,
+            protocol: self.protocol,
+            video_codec: normalize_codec(self.vcodec),
+            audio_codec: normalize_codec(self.acodec),
+            width: self.width,
+            height: self.height,
+            resolution: self.resolution,
+            fps: self.fps,
+            dynamic_range: self.dynamic_range,
+            total_bitrate: self.tbr,
+            video_bitrate: self.vbr,
+            audio_bitrate: self.abr,
+            filesize_approx: self.filesize.is_none() && self.filesize_approx.is_some(),
+            filesize: self.filesize.or(self.filesize_approx),
+        }
+    }
+}
+
+fn subtitle_tracks(tracks: Option<BTreeMap<String, Vec<RawSubtitle>>>) -> Vec<SubtitleTrack> {
+    tracks
+        .unwrap_or_default()
+        .into_iter()
+        // yt-dlp lists live chat replays as a subtitle track; it is not a caption.
+        .filter(|(language, _)| language != "live_chat")
+        .map(|(language, entries)| {
+            let name = entries.iter().find_map(|entry| entry.name.clone());
+            let mut formats: Vec<String> =
+                entries.into_iter().filter_map(|entry| entry.ext).collect();
+            formats.dedup();
+            SubtitleTrack {
+                language,
+                name,
+                formats,
+            }
+        })
+        .collect()
+}
+
+/// yt-dlp uses the literal `none` for a missing stream rather than leaving it out.
+fn normalize_codec(codec: Option<String>) -> Option<String> {
+    codec.filter(|value| !value.is_empty() && value != "none")
+}
+
+fn lenient_f64<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(Option::<serde_json::Value>::deserialize(deserializer)?.and_then(|value| value.as_f64()))
+}
+
+fn lenient_u64<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(lenient_f64(deserializer)?
+        .filter(|value| *value >= 0.0)
+        .map(|value| value.round() as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_media_info;
+
+    const YOUTUBE_VIDEO: &str = include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/probe/youtube_video.json"
+    ));
+    const BILIBILI_VIDEO: &str = include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/probe/bilibili_video.json"
+    ));
+    const YOUTUBE_PLAYLIST: &str = include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/probe/youtube_playlist_flat.json"
+    ));
+
+    #[test]
+    fn parses_youtube_video_fixture() {
+        let info = parse_media_info(YOUTUBE_VIDEO).expect("fixture should parse");
+
+        assert_eq!(info.id, "dQw4w9WgXcQ");
+        assert_eq!(info.extractor.as_deref(), Some("Youtube"));
+        assert_eq!(info.uploader.as_deref(), Some("Rick Astley"));
+        assert_eq!(info.duration, Some(212.0));
+        assert!(!info.is_playlist);
+        assert_eq!(info.thumbnails.len(), 2);
+        assert_eq!(info.chapters.len(), 2);
+        assert_eq!(info.chapters[1].title, "Chorus");
+
+        let languages: Vec<&str> = info
+            .subtitles
+            .iter()
+            .map(|track| track.language.as_str())
+            .collect();
+        assert_eq!(languages, ["en", "ja"]);
+        assert_eq!(info.subtitles[0].formats, ["vtt", "srv1", "json3"]);
+        assert_eq!(info.automatic_captions.len(), 1);
+
+        let audio = info
+            .formats
+            .iter()
+            .find(|format| format.format_id == "251")
+            .expect("opus audio format");
+        assert_eq!(audio.video_codec, None);
+        assert_eq!(audio.audio_codec.as_deref(), Some("opus"));
+        assert_eq!(audio.filesize, Some(3_437_753));
+
+        let video = info
+            .formats
+            .iter()
+            .find(|format| format.format_id == "399")
+            .expect("av1 video format");
+        assert_eq!(video.video_codec.as_deref(), Some("av01.0.08M.08"));
+        assert_eq!(video.audio_codec, None);
+        assert_eq!((video.width, video.height), (Some(1920), Some(1080)));
+        assert_eq!(video.fps, Some(25.0));
+        assert!(video.filesize_approx);
+    }
+
+    #[test]
+    fn parses_fixture_with_missing_optional_fields() {
+        let info = parse_media_info(BILIBILI_VIDEO).expect("fixture should parse");
+
+        assert_eq!(info.extractor.as_deref(), Some("BiliBili"));
+        assert!(info.chapters.is_empty());
+        assert!(info.subtitles.is_empty());
+        assert_eq!(info.formats.len(), 3);
+        assert!(info.formats.iter().all(|format| format.filesize.is_some()));
+    }
+
+    #[test]
+    fn parses_flat_playlist_fixture() {
+        let info = parse_media_info(YOUTUBE_PLAYLIST).expect("fixture should parse");
+
+        assert!(info.is_playlist);
+        assert!(info.formats.is_empty());
+        assert_eq!(info.entries.len(), 3);
+        assert_eq!(info.entries[2].title.as_deref(), Some("Third upload"));
+    }
+
+    #[test]
+    fn rejects_non_json_output() {
+        assert!(parse_media_info("ERROR: Unsupported URL").is_err());
+    }
+}
@@ -1,4 +1,7 @@
+use serde::Deserialize;
+
 const PROGRESS_PREFIX: &str = "download:__YTDLPX__:";
+const DOWNLOADED_ITEM_PREFIX: &str = "__YTDLPX_ITEM__:";
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct ProgressInfo {
@@ -11,14 +14,62 @@ pub struct ProgressInfo {
     pub raw: String,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlaylistItemInfo {
+    pub index: u32,
+    pub total: u32,
+}
+
+/// One finished item as reported by [`downloaded_item_print_template`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct DownloadedItem {
+    #[serde(rename = "extractor_key")]
+    pub extractor: Option<String>,
+    pub id: Option<String>,
+    pub title: Option<String>,
+    pub filepath: Option<String>,
+    pub webpage_url: Option<String>,
+    /// `none` when the final file carries no video stream.
+    #[serde(rename = "vcodec")]
+    pub video_codec: Option<String>,
+}
+
 pub fn progress_template_value() -> &'static str {
     "download:__YTDLPX__:%(progress._percent_str)s|%(progress._speed_str)s|%(progress._eta_str)s|%(progress._total_bytes_str)s"
 }
 
+/// `--print` value emitting one JSON line per item once it reaches its final path.
+pub fn downloaded_item_print_template() -> &'static str {
+    "after_move:__YTDLPX_ITEM__:%(.{extractor_key,id,title,filepath,webpage_url,vcodec})j"
+}
+
+pub fn parse_downloaded_item_line(line: &str) -> Option<DownloadedItem> {
+    let payload = line.trim().strip_prefix(DOWNLOADED_ITEM_PREFIX)?;
+    serde_json::from_str(payload).ok()
+}
+
 pub fn parse_progress_line(line: &str) -> Option<ProgressInfo> {
     parse_template_progress_line(line).or_else(|| parse_legacy_download_line(line))
 }
 
+/// Parses yt-dlp's `[download] Downloading item 3 of 10` marker (`video` on
+/// older releases) that precedes every playlist entry.
+pub fn parse_playlist_item_line(line: &str) -> Option<PlaylistItemInfo> {
+    let rest = line.trim().strip_prefix("[download]")?.trim_start();
+    let rest = rest
+        .strip_prefix("Downloading item ")
+        .or_else(|| rest.strip_prefix("Downloading video "))?;
+    let (index, total) = rest.split_once(" of ")?;
+
+    let index = index.trim().parse::<u32>().ok()?;
+    let total = total.trim().parse::<u32>().ok()?;
+    if index == 0 || index > total {
+        return None;
+    }
+
+    Some(PlaylistItemInfo { index, total })
+}
+
 fn parse_template_progress_line(line: &str) -> Option<ProgressInfo> {
     if !line.starts_with(PROGRESS_PREFIX) {
         return None;
@@ -160,7 +211,10 @@ fn normalize_token(value: &str) -> Option<String> {
 
 #[cfg(test)]
 mod tests {
-    use super::{parse_progress_line, progress_template_value};
+    use super::{
+        parse_downloaded_item_line, parse_playlist_item_line, parse_progress_line,
+        progress_template_value, DownloadedItem, PlaylistItemInfo,
+    };
 
     #[test]
     fn parses_template_progress_line() {
@@ -185,9 +239,52 @@ mod tests {
         assert_eq!(parsed.status.as_deref(), Some("finished"));
     }
 
+    #[test]
+    fn parses_playlist_item_markers() {
+        assert_eq!(
+            parse_playlist_item_line("[download] Downloading item 3 of 12"),
+            Some(PlaylistItemInfo {
+                index: 3,
+                total: 12
+            })
+        );
+        assert_eq!(
+            parse_playlist_item_line("[download] Downloading video 1 of 2"),
+            Some(PlaylistItemInfo { index: 1, total: 2 })
+        );
+        assert!(parse_playlist_item_line("[download] Downloading playlist: Mix").is_none());
+        assert!(parse_progress_line("[download] Downloading item 3 of 12").is_none());
+    }
+
     #[test]
     fn progress_template_contains_expected_prefix() {
         let template = progress_template_value();
         assert!(template.starts_with("download:__YTDLPX__:"));
     }
+
+    #[test]
+    fn parses_downloaded_item_line() {
+        let line = r#"__YTDLPX_ITEM__:{"extractor_key": "Youtube", "id": "dQw4w9WgXcQ", "title": "Never Gonna Give You Up", "filepath": "/tmp/out/Never Gonna Give You Up [dQw4w9WgXcQ].mp4", "webpage_url": "https://www.youtube.com/watch?v=dQw4w9WgXcQ"}"#;
+
+        assert_eq!(
+            parse_downloaded_item_line(line),
+            Some(DownloadedItem {
+                extractor: Some("Youtube".into()),
+                id: Some("dQw4w9WgXcQ".into()),
+                title: Some("Never Gonna Give You Up".into()),
+                filepath: Some("/tmp/out/Never Gonna Give You Up [dQw4w9WgXcQ].mp4".into()),
+                webpage_url: Some("https://www.youtube.com/watch?v=dQw4w9WgXcQ".into()),
+                video_codec: None,
+            })
+        );
+        assert_eq!(
+            parse_downloaded_item_line(r#"__YTDLPX_ITEM__:{"id": "abc"}"#).and_then(|item| item.id),
+            Some("abc".into())
+        );
+        assert_eq!(
+            parse_downloaded_item_line("[download] 100% of 3.2MiB"),
+            None
+        );
+        assert_eq!(parse_downloaded_item_line("__YTDLPX_ITEM__:not json"), None);
+    }
 }
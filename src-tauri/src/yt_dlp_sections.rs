@@ -0,0 +1,326 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum DownloadSection {
+    /// Start and end accept `1:02:03`, `02:03`, `90`, `90s` or `1h2m3s`. A
+    /// missing start means the beginning, a missing end (or `inf`) the end.
+    Time {
+        start: Option<String>,
+        end: Option<String>,
+    },
+    /// Regex matched against chapter titles.
+    Chapter { pattern: String },
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SectionOptions {
+    pub sections: Vec<DownloadSection>,
+    /// Re-encodes around the cut points so clips start on the exact frame.
+    pub force_keyframes_at_cuts: bool,
+}
+
+impl SectionOptions {
+    /// yt-dlp downloads sections and forces keyframes through ffmpeg.
+    pub fn requires_ffmpeg(&self) -> bool {
+        !self.sections.is_empty() || self.force_keyframes_at_cuts
+    }
+
+    /// A chapter regex can match several chapters, each written as its own file.
+    pub fn may_write_several_files(&self) -> bool {
+        self.sections.len() > 1
+            || self
+                .sections
+                .iter()
+                .any(|section| matches!(section, DownloadSection::Chapter { .. }))
+    }
+}
+
+/// Validates every section and returns the `--download-sections` values.
+pub fn section_specs(options: &SectionOptions) -> Result<Vec<String>, String> {
+    options
+        .sections
+        .iter()
+        .map(|section| match section {
+            DownloadSection::Time { start, end } => time_range_spec(start, end),
+            DownloadSection::Chapter { pattern } => chapter_spec(pattern),
+        })
+        .collect()
+}
+
+fn time_range_spec(start: &Option<String>, end: &Option<String>) -> Result<String, String> {
+    let start = match start.as_deref().map(str::trim) {
+        None | Some("") => 0.0,
+        Some(value) => parse_timestamp(value)?,
+    };
+    let end = match end.as_deref().map(str::trim) {
+        None | Some("") => None,
+        Some(value) if value.eq_ignore_ascii_case("inf") => None,
+        Some(value) => Some(parse_timestamp(value)?),
+    };
+
+    match end {
+        Some(end) if end <= start => Err(format!(
+            "片段结束时间 ({}) 必须晚于开始时间 ({})",
+            format_seconds(end),
+            format_seconds(start)
+        )),
+        Some(end) => Ok(format!(
+            "*{}-{}",
+            format_seconds(start),
+            format_seconds(end)
+        )),
+        None => Ok(format!("*{}-inf", format_seconds(start))),
+    }
+}
+
+/// A light sanity check; the pattern is a Python regex, which Rust cannot
+/// compile faithfully, so only structural mistakes are caught here.
+fn chapter_spec(pattern: &str) -> Result<String, String> {
+    let pattern = pattern.trim();
+    if pattern.is_empty() {
+        return Err("章节匹配规则不能为空".into());
+    }
+    if pattern.starts_with('*') {
+        return Err(format!("章节匹配规则不能以 * 开头: {pattern}"));
+    }
+    if pattern.chars().any(char::is_control) {
+        return Err("章节匹配规则不能包含控制字符".into());
+    }
+
+    let mut depth = 0i32;
+    let mut in_class = false;
+    let mut chars = pattern.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' if chars.next().is_none() => {
+                return Err(format!("章节匹配规则以转义符结尾: {pattern}"));
+            }
+            '\\' => {}
+            '[' if !in_class => in_class = true,
+            ']' if in_class => in_class = false,
+            '(' if !in_class => depth += 1,
+            ')' if !in_class => {
+                depth -= 1;
+                if depth < 0 {
+                    break;
+                }
+            }
+            _ => {}
+        }
+    }
+    if depth != 0 || in_class {
+        return Err(format!("章节匹配规则的括号不匹配: {pattern}"));
+    }
+
+    Ok(pattern.to_string())
+}
+
+pub fn parse_timestamp(value: &str) -> Result<f64, String> {
+    let trimmed = value.trim().to_ascii_lowercase();
+    let invalid = || format!("无法识别的时间: {value}");
+
+    if trimmed.is_empty() {
+        return Err(invalid());
+    }
+
+    let seconds = if trimmed.contains(':') {
+        parse_clock(&trimmed)
+    } else {
+        parse_units(&trimmed)
+    }
+    .ok_or_else(invalid)?;
+
+    if seconds.is_finite() && seconds >= 0.0 {
+        Ok(seconds)
+    } else {
+        Err(invalid())
+    }
+}
+
+/// `[[h:]m:]s` where every field after the first is below 60.
+fn parse_clock(value: &str) -> Option<f64> {
+    let fields: Vec<&str> = value.split(':').collect();
+    if fields.len() > 3 {
+        return None;
+    }
+
+    let (last, leading) = fields.split_last()?;
+    let mut total = 0.0;
+    for (index, field) in leading.iter().enumerate() {
+        if field.is_empty() || !field.chars().all(|ch| ch.is_ascii_digit()) {
+            return None;
+        }
+        let number: f64 = field.parse().ok()?;
+        if index > 0 && number >= 60.0 {
+            return None;
+        }
+        total = total * 60.0 + number;
+    }
+
+    let seconds = parse_decimal(last)?;
+    if seconds >= 60.0 {
+        return None;
+    }
+
+    Some(total * 60.0 + seconds)
+}
+
+/// Plain seconds (`90`, `90.5`) or descending unit groups (`1h2m3s`, `90s`).
+fn parse_units(value: &str) -> Option<f64> {
+    if let Some(seconds) = parse_decimal(value) {
+        return Some(seconds);
+    }
+
+    let mut total = 0.0;
+    let mut rest = value;
+    let mut last_rank = usize::MAX;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|ch: char| !(ch.is_ascii_digit() || ch == '.'))
+            .unwrap_or(rest.len());
+        let number = parse_decimal(&rest[..digits])?;
+        let (rank, factor) = match rest[digits..].chars().next()? {
+            'h' => (2, 3600.0),
+            'm' => (1, 60.0),
+            's' => (0, 1.0),
+            _ => return None,
+        };
+        if rank >= last_rank {
+            return None;
+        }
+
+        last_rank = rank;
+        total += number * factor;
+        rest = &rest[digits + 1..];
+    }
+
+    Some(total)
+}
+
+fn parse_decimal(value: &str) -> Option<f64> {
+    let valid = !value.is_empty()
+        && value.chars().all(|ch| ch.is_ascii_digit() || ch == '.')
+        && value.matches('.').count() <= 1
+        && value != ".";
+    valid.then(|| value.parse().ok()).flatten()
+}
+
+fn format_seconds(seconds: f64) -> String {
+    let rounded = (seconds * 1000.0).round() / 1000.0;
+    format!("{rounded}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_timestamp, section_specs, DownloadSection, SectionOptions};
+
+    fn time(start: Option<&str>, end: Option<&str>) -> DownloadSection {
+        DownloadSection::Time {
+            start: start.map(str::to_string),
+            end: end.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn parses_flexible_timestamps() {
+        let cases = [
+            ("1:02:03", 3723.0),
+            ("02:03", 123.0),
+            ("90:00", 5400.0),
+            ("0:00:01.5", 1.5),
+            ("90", 90.0),
+            ("90.25", 90.25),
+            ("90s", 90.0),
+            ("1m30s", 90.0),
+            ("1h2m3s", 3723.0),
+            ("2H", 7200.0),
+            ("1.5m", 90.0),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(parse_timestamp(input), Ok(expected), "{input}");
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_timestamps() {
+        for input in [
+            "", "abc", "1:60", "1:2:3:4", ":30", "1::2", "-5", "30s1m", "1m1m", "1x", "1.2.3",
+            "1h 2m",
+        ] {
+            assert!(
+                parse_timestamp(input).is_err(),
+                "{input} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn builds_section_specs() {
+        let options = SectionOptions {
+            sections: vec![
+                time(Some("1:02:03"), Some("1:02:33")),
+                time(None, Some("90s")),
+                time(Some("1h"), Some("inf")),
+                DownloadSection::Chapter {
+                    pattern: "(?i)^intro$".into(),
+                },
+            ],
+            force_keyframes_at_cuts: false,
+        };
+
+        assert_eq!(
+            section_specs(&options).expect("sections should compile"),
+            ["*3723-3753", "*0-90", "*3600-inf", "(?i)^intro$"]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_sections() {
+        let cases = [
+            time(Some("2:00"), Some("1:00")),
+            time(Some("90"), Some("1:30")),
+            time(Some("soon"), None),
+            DownloadSection::Chapter {
+                pattern: " ".into(),
+            },
+            DownloadSection::Chapter {
+                pattern: "*10-20".into(),
+            },
+            DownloadSection::Chapter {
+                pattern: "(intro".into(),
+            },
+            DownloadSection::Chapter {
+                pattern: "[a-z".into(),
+            },
+        ];
+
+        for section in cases {
+            let options = SectionOptions {
+                sections: vec![section.clone()],
+                force_keyframes_at_cuts: false,
+            };
+            assert!(
+                section_specs(&options).is_err(),
+                "{section:?} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn deserializes_tagged_sections() {
+        let options: SectionOptions = serde_json::from_value(serde_json::json!({
+            "sections": [
+                { "kind": "time", "start": "1:00", "end": "1:30" },
+                { "kind": "chapter", "pattern": "Chorus" }
+            ],
+            "forceKeyframesAtCuts": true
+        }))
+        .expect("should deserialize");
+
+        assert_eq!(options.sections.len(), 2);
+        assert!(options.force_keyframes_at_cuts);
+    }
+}